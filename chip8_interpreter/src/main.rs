@@ -19,10 +19,19 @@ fn main() {
 
     let init_state = main_state::MainState::new(scale as f32);
 
+    let storage = Storage::in_config_dir("sfmx_chip8").unwrap_or_else(|error| {
+        eprintln!("{}, the storage is kept in memory", error);
+        Storage::in_memory()
+    });
 
-    Application::build()
+    let result = Application::build()
         .with_initial_state(init_state)
         .with_states_data(init_data)
+        .with_storage(storage)
         .build(app_data)
         .run();
+
+    if let Err(error) = result {
+        eprintln!("{}", error);
+    }
 }
@@ -1,11 +1,36 @@
-
-use std::time::Duration;
-
 use sfmx::prelude::*;
 use crate::chip8::{Chip8, self};
-use rfd::{FileDialog, MessageDialog};
-use rodio::{source, Source, OutputStream};
-use rodio;
+use rfd::FileDialog;
+
+/// Looping tone played while the sound timer runs
+struct Beep {
+    // Dropped before the buffer it plays
+    sound: Sound<'static>,
+    _buffer: SfBox<SoundBuffer>
+}
+
+impl Beep {
+    fn new(buffer: &SoundBuffer) -> Self {
+        let buffer = buffer.to_owned();
+        // The buffer is owned by the beep and outlives the sound
+        let mut sound = Sound::with_buffer(unsafe { &*(&*buffer as *const SoundBuffer) });
+        sound.set_looping(true);
+        Self {
+            sound,
+            _buffer: buffer
+        }
+    }
+
+    fn set_playing(&mut self, playing: bool) {
+        let is_playing = self.sound.status() == SoundStatus::PLAYING;
+        if playing && !is_playing {
+            self.sound.play();
+        }
+        else if !playing && is_playing {
+            self.sound.stop();
+        }
+    }
+}
 
 pub struct MainState {
     chip8: Chip8,
@@ -13,9 +38,10 @@ pub struct MainState {
     texture_scale: f32,
     running_program: bool,
     ui_manager: UiManager<&'static str>,
-    stream_handle: rodio::OutputStreamHandle,
-    _stream: OutputStream,
+    beep: Option<Beep>,
     keyboard_mapping: [Key; 16],
+    /// CRT effect of the display, toggled with F2
+    display_effect: PostProcess,
 
     cycle_time: f32,
     dt: f32
@@ -23,8 +49,6 @@ pub struct MainState {
 
 impl MainState {
     pub fn new(texture_scale: f32) -> Self {
-        let (_stream, stream_handle) = rodio::OutputStream::try_default().unwrap();
-
         let mut keyboard_mapping = [Key::Num0; 16];
        keyboard_mapping[chip8::Key::Key0 as usize] = Key::X;
        keyboard_mapping[chip8::Key::Key1 as usize] = Key::Num1;
@@ -50,32 +74,32 @@ impl MainState {
             texture_scale,
             running_program: false,
             ui_manager: UiManager::new(),
-            stream_handle,
-            _stream,
+            beep: None,
             keyboard_mapping,
+            display_effect: PostProcess::new(),
             cycle_time: 1.0 / 60.0,
             dt: 0.0
         }
     }
 
-    fn execute_next_instruction(&mut self) {
+    fn execute_next_instruction(&mut self, localization: &Localization) -> Transition<()> {
+        let mut trans = Transition::None;
         if self.running_program {
             if let Err(_) = self.chip8.clock() {
-                MessageDialog::new()
-                .set_buttons(rfd::MessageButtons::Ok)
-                .set_level(rfd::MessageLevel::Error)
-                .set_title("Failed to run program")
-                .set_description("Cannot continue to run the program.\nPlease make sure the program is for Chip-8")
-                .show();
+                trans = Transition::Add(Box::new(DialogState::message_box(
+                    &localization.tr("error.title"),
+                    &localization.tr("error.text"),
+                    &[&localization.tr("dialog.ok")]
+                )));
 
                 self.chip8.reset();
             }
         }
 
-        if self.chip8.play_sound() {
-            let source = source::SineWave::new(440.0).amplify(0.2).take_duration(Duration::from_secs(1));
-            self.stream_handle.play_raw(source).unwrap();
+        if let Some(beep) = self.beep.as_mut() {
+            beep.set_playing(self.chip8.play_sound());
         }
+        trans
     }
 
     fn update_screen(&mut self) {
@@ -100,7 +124,7 @@ impl MainState {
         }
     }
 
-    fn draw_keyboard(&self, font: &SfBox<Font>, target: &mut dyn RenderTarget) {
+    fn draw_keyboard(&self, font: &FontAsset, target: &mut dyn RenderTarget) {
         let mut text = DynamicText::new();
         text.set_font(&font);
         let cell_size = 50.0;
@@ -125,7 +149,21 @@ impl MainState {
 impl State<()> for MainState {
     fn on_init(&mut self, state_data: &mut StateData<()>) {
 
+        // The assets are next to the executable of a shipped build, in the crate while developing
+        #[cfg(debug_assertions)]
+        state_data.assets_manager.mount(DirectorySource::new(env!("CARGO_MANIFEST_DIR")));
+        match DirectorySource::next_to_executable() {
+            Ok(source) => state_data.assets_manager.mount(source),
+            Err(error) => eprintln!("{}", error)
+        }
         state_data.assets_manager.load_asset(AssetType::Font, "font".to_string(), "assets/slkscr.ttf");
+        state_data.assets_manager.load_asset(AssetType::SoundBuffer, "beep".to_string(), "assets/beep.wav");
+        self.beep = state_data.assets_manager.get_asset::<SfBox<SoundBuffer>, _>(AssetType::SoundBuffer, "beep".to_string())
+            .ok()
+            .map(|buffer| Beep::new(buffer));
+        if let Err(error) = state_data.localization.load_language("en", state_data.assets_manager.file_system(), "assets/lang/en.lang") {
+            eprintln!("{}", error);
+        }
 
         let font = state_data.assets_manager.get_asset(AssetType::Font, "font".to_string()).unwrap();
 
@@ -138,7 +176,7 @@ impl State<()> for MainState {
         load_btn.set_font(font);
         load_btn.set_size((100.0, 50.0).into());
         load_btn.set_color(Color::rgba(0, 0, 0, 0));
-        load_btn.set_text("Load");
+        load_btn.bind_key(TextKey::new("button.load"));
         load_btn.set_position((50.0, self.chip8.get_display().height() as f32 * self.texture_scale + 10.0).into());
 
         self.ui_manager.add_widget("load", load_btn);
@@ -147,14 +185,25 @@ impl State<()> for MainState {
         load_btn.set_font(font);
         load_btn.set_size((100.0, 50.0).into());
         load_btn.set_color(Color::rgba(0, 0, 0, 0));
-        load_btn.set_text("Help");
+        load_btn.bind_key(TextKey::new("button.help"));
         load_btn.set_position((200.0, self.chip8.get_display().height() as f32 * self.texture_scale + 10.0).into());
 
         self.ui_manager.add_widget("help", load_btn);
+        self.ui_manager.localize(&state_data.localization);
+
+        match PostPass::crt() {
+            Ok(crt) => self.display_effect.add_pass(crt.with_enabled(state_data.storage.get_or("crt_effect", true))),
+            Err(error) => eprintln!("{}", error)
+        }
     }
 
-    fn on_event(&mut self, event: Event, _: &mut StateData<()>) -> Transition<()> {
+    fn on_event(&mut self, event: Event, state_data: &mut StateData<()>) -> Transition<()> {
         self.ui_manager.on_event(event);
+        // Without the pass, e.g. when its shader failed to compile, the preference is kept
+        if let (Event::KeyPressed { code: Key::F2, .. }, Some(_)) = (event, self.display_effect.pass("crt")) {
+            let enabled = self.display_effect.toggle("crt");
+            state_data.storage.set("crt_effect", &enabled).unwrap();
+        }
         let mut key_state = None;
         if let Event::KeyPressed { code, ..} = event {
             key_state = Some((code, true));
@@ -177,53 +226,56 @@ impl State<()> for MainState {
     fn on_update(&mut self, state_data: &mut StateData<()>) -> Transition<()> {
 
         //self.handle_input();
+        let mut trans = Transition::None;
         if self.dt > self.cycle_time {
-            self.execute_next_instruction();
+            trans = self.execute_next_instruction(&state_data.localization);
             self.update_screen();
             self.dt = 0.0
         }
         else {
             self.dt += state_data.delta_time;
         }
+        self.display_effect.update(state_data.delta_time);
 
         if self.ui_manager.get_widget(&"load").unwrap().is_clicked() {
-            let file = FileDialog::new()
-                .add_filter("chip8 exec", &["ch8"])
-                .pick_file();
-            if let Some(path) = file {
+            let mut dialog = FileDialog::new()
+                .add_filter("chip8 exec", &["ch8"]);
+            if let Some(directory) = state_data.storage.get::<String>("last_rom_directory") {
+                dialog = dialog.set_directory(directory);
+            }
+
+            if let Some(path) = dialog.pick_file() {
                 self.running_program = true;
                 self.chip8.reset();
                 self.chip8.load_program(path.to_str().unwrap()).unwrap();
+                if let Some(directory) = path.parent() {
+                    state_data.storage.set("last_rom_directory", &directory.to_string_lossy()).unwrap();
+                }
             }
         }
 
         if self.ui_manager.get_widget(&"help").unwrap().is_clicked() {
-            MessageDialog::new()
-            .set_description(
-                "This is a Chip-8 emulator\nYou can choose a program to run with Load button\
-                In the top right the Chip-8 keyboard layout, this layout is mapped to the following layout\
-                \n1 2 3 4\nA Z E R\nQ S D F\nW X C V"
-            )
-            .set_buttons(rfd::MessageButtons::Ok)
-            .set_title("Help")
-            .set_level(rfd::MessageLevel::Info)
-            .show();
+            trans = Transition::Add(Box::new(DialogState::message_box(
+                &state_data.localization.tr("help.title"),
+                &state_data.localization.tr("help.text"),
+                &[&state_data.localization.tr("dialog.ok")]
+            )));
         }
 
         self.ui_manager.reset();
+        self.ui_manager.localize(&state_data.localization);
 
-        Transition::None
+        trans
     }
 
     fn on_render(&mut self, state_data: &mut StateData<()>, target: &mut dyn RenderTarget) -> bool {
         target.clear(Color::BLUE);
         
-        let mut sprite = Sprite::new();
-        sprite.set_texture(&self.display_texture, true);
-        sprite.set_scale((self.texture_scale, self.texture_scale));
-        target.draw(&sprite);
+        let mut states = RenderStates::DEFAULT;
+        states.transform.scale(self.texture_scale, self.texture_scale);
+        self.display_effect.process(&self.display_texture, target, &states);
 
-        let font: &SfBox<Font> = state_data.assets_manager.get_asset(AssetType::Font, "font".to_string()).unwrap();
+        let font: &FontAsset = state_data.assets_manager.get_asset(AssetType::Font, "font".to_string()).unwrap();
         self.draw_keyboard(font, target);
         
         self.ui_manager.draw(target);
@@ -24,8 +24,15 @@ type Data = ();
 
 impl State<Data> for TestState {
     fn on_init(&mut self, state_data: &mut StateData<Data>) {
-        state_data.assets_manager.load_asset(AssetType::Font, "font".to_string(), "/Library/Fonts/Arial.ttf");
-        let font = state_data.assets_manager.get_asset(AssetType::Font, "font".to_string()).unwrap();
+        // The assets are next to the executable of a shipped build, in the crate while developing
+        #[cfg(debug_assertions)]
+        state_data.assets_manager.mount(DirectorySource::new(env!("CARGO_MANIFEST_DIR")));
+        match DirectorySource::next_to_executable() {
+            Ok(source) => state_data.assets_manager.mount(source),
+            Err(error) => eprintln!("{}", error)
+        }
+        state_data.assets_manager.load_by_extension("font".to_string(), "assets/slkscr.ttf");
+        let font = state_data.assets_manager.get::<FontAsset>("font".to_string()).unwrap();
 
         let mut button = Button::new();
         button.set_text("Click me!");
@@ -112,9 +119,13 @@ fn main() {
 
     let init_state = TestState::new();
 
-    Application::build()
+    let result = Application::build()
         .with_initial_state(init_state)
         .with_states_data(init_data)
         .build(app_data)
         .run();
+
+    if let Err(error) = result {
+        eprintln!("{}", error);
+    }
 }
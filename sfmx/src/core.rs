@@ -3,8 +3,20 @@ pub mod application;
 pub mod state_machine;
 pub mod timer;
 pub mod assets_manager;
+pub mod file_system;
+pub mod states_info;
+pub mod tasks;
+pub mod storage;
+pub mod localization;
+pub mod tween;
 
 pub use application::*;
 pub use state_machine::*;
 pub use timer::*;
-pub use assets_manager::*;
\ No newline at end of file
+pub use assets_manager::*;
+pub use file_system::*;
+pub use states_info::*;
+pub use tasks::*;
+pub use storage::*;
+pub use localization::*;
+pub use tween::*;
\ No newline at end of file
@@ -1,11 +1,8 @@
 use crate::sfml_export::*;
-use super::state_machine::{StateMachine, StateData, State};
+use super::state_machine::{StateMachine, StateData, State, Transition};
 use super::timer::Timer;
-
-pub(crate) enum AppSignal {
-    Quit,
-    None
-}
+use super::storage::Storage;
+use crate::rendering::frame_capture::capture_window;
 
 pub struct CursorSettings {
     pub cursor_type: CursorType,
@@ -50,14 +47,16 @@ impl Default for AppData {
 
 pub struct AppBuilder<Data, S: State<Data>> {
     states_data: Option<StateData<Data>>,
-    initial_state: Option<S>
+    initial_state: Option<S>,
+    storage: Option<Storage>
 }
 
 impl<Data, S: State<Data> + 'static> AppBuilder<Data, S> {
     pub fn new() -> Self {
         Self {
             states_data: None,
-            initial_state: None
+            initial_state: None,
+            storage: None
         }
     }
 
@@ -71,6 +70,11 @@ impl<Data, S: State<Data> + 'static> AppBuilder<Data, S> {
         self
     }
 
+    pub fn with_storage(mut self, storage: Storage) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
     pub fn build(self, app_data: AppData) -> Application<Data> {
         let mut window = RenderWindow::new(
             app_data.win_size,
@@ -83,14 +87,20 @@ impl<Data, S: State<Data> + 'static> AppBuilder<Data, S> {
 
         let mut states_data = self.states_data.expect("No data provided for the states");
         states_data.render_target_size = app_data.win_size;
+        if let Some(storage) = self.storage {
+            states_data.storage = storage;
+        }
+
+        let state_machine = StateMachine::root(
+            Box::new(self.initial_state.expect("Initial state is missing")),
+            &mut states_data
+        );
 
         Application {
             window,
             cursor: None,
-            state_machine: StateMachine::new(
-                Box::new(self.initial_state.expect("Initial state is missing")),
-                states_data
-            )
+            state_machine,
+            states_data
         }
     }
 }
@@ -98,7 +108,8 @@ impl<Data, S: State<Data> + 'static> AppBuilder<Data, S> {
 pub struct Application<Data> {
     window: RenderWindow,
     cursor: Option<SfBox<Cursor>>,
-    state_machine: StateMachine<Data>
+    state_machine: StateMachine<Data>,
+    states_data: StateData<Data>
 }
 
 impl<Data> Application<Data> {
@@ -106,7 +117,16 @@ impl<Data> Application<Data> {
         AppBuilder::new()
     }
 
-    pub fn run(&mut self) {
+    pub fn states_data(&self) -> &StateData<Data> {
+        &self.states_data
+    }
+
+    pub fn states_data_mut(&mut self) -> &mut StateData<Data> {
+        &mut self.states_data
+    }
+
+    /// Runs until the window is closed, fails if the preferences cannot be written on exit
+    pub fn run(&mut self) -> Result<(), String> {
 
         let timer = Timer::new();
 
@@ -114,7 +134,7 @@ impl<Data> Application<Data> {
         while self.window.is_open() {
 
             let new_time = timer.elapsed().as_secs_f32();
-            self.state_machine.states_data.delta_time = new_time - current_time;
+            self.states_data.delta_time = new_time - current_time;
             current_time = new_time;
 
             self.handle_events();
@@ -122,7 +142,7 @@ impl<Data> Application<Data> {
 
             self.render();
 
-            if let Some(settings) = &self.state_machine.states_data.cursor {
+            if let Some(settings) = &self.states_data.cursor {
                 self.cursor = Cursor::from_system(settings.cursor_type);
                 if let Some(cursor) = &self.cursor {
                     unsafe {
@@ -132,44 +152,86 @@ impl<Data> Application<Data> {
                 self.window.set_mouse_cursor_grabbed(settings.is_grabbed);
                 self.window.set_mouse_cursor_visible(settings.is_visible);
             }
-            self.state_machine.states_data.cursor = None;
+            self.states_data.cursor = None;
 
         }
 
-        self.cleanup();
+        self.cleanup()
     }
 
     fn handle_events(&mut self) {
         while let Some(event) = self.window.poll_event() {
             if event == Event::Closed {
-                self.window.close();
+                // Goes through the states guards, the window is closed when the transition is applied
+                self.state_machine.push_transition(Transition::Quit);
             }
             else {
-                let app_signal = self.state_machine.on_event(event);
-                self.handle_signal(app_signal);
+                self.states_data.capture.on_event(event);
+                self.state_machine.on_event(event, &mut self.states_data);
             }
         }
     }
 
     fn update(&mut self) {
-        let app_signal = self.state_machine.on_update();
-        self.handle_signal(app_signal);
+        self.states_data.tasks.poll();
+        self.states_data.post_process.update(self.states_data.delta_time);
+        let trans = self.state_machine.on_update(&mut self.states_data);
+        self.handle_transition(trans);
     }
 
     fn render(&mut self) {
-       if self.state_machine.on_render(&mut self.window) {
+        let rendered = if self.states_data.post_process.is_active() {
+            self.render_post_processed()
+        }
+        else {
+            self.state_machine.on_render(&mut self.states_data, &mut self.window)
+        };
+
+       if rendered {
+            let window = &self.window;
+            if let Err(error) = self.states_data.capture.on_frame(self.states_data.delta_time, || capture_window(window)) {
+                self.states_data.capture.add_error(error);
+            }
             self.window.display();
        }
     }
 
-    fn handle_signal(&mut self, signal: AppSignal) {
-        match signal {
-            AppSignal::Quit => self.window.close(),
-            _ => {}
+    fn render_post_processed(&mut self) -> bool {
+        let mut scene = match self.states_data.post_process.begin_scene(&self.window) {
+            Some(scene) => scene,
+            None => return self.state_machine.on_render(&mut self.states_data, &mut self.window)
+        };
+
+        let rendered = self.state_machine.on_render(&mut self.states_data, &mut scene);
+        if rendered {
+            // The scene is transparent where nothing was drawn
+            self.window.clear(Color::BLACK);
+            self.states_data.post_process.present(scene, &mut self.window);
         }
+        else {
+            self.states_data.post_process.end_scene(scene);
+        }
+        rendered
     }
 
-    fn cleanup(&mut self) {
-        self.state_machine.terminate();
+    fn handle_transition(&mut self, trans: Transition<Data>) {
+        // The root machine has no parent, only quitting is left to handle
+        if let Transition::Quit = trans {
+            self.window.close();
+        }
+
+        if self.state_machine.is_empty() {
+            self.window.close();
+        }
+    }
+
+    fn cleanup(&mut self) -> Result<(), String> {
+        self.state_machine.terminate(&mut self.states_data);
+        if self.states_data.capture.is_recording() {
+            if let Err(error) = self.states_data.capture.stop_recording() {
+                self.states_data.capture.add_error(error);
+            }
+        }
+        self.states_data.storage.flush()
     }
 }
\ No newline at end of file
@@ -1,8 +1,14 @@
 use crate::sfml_export::*;
 use std::collections::HashMap;
-use std::fmt::Debug;
+use std::fmt::{Debug, Display};
 use std::hash::Hash;
 use std::collections::VecDeque;
+use std::any::{Any, TypeId};
+use std::ops::Deref;
+use std::rc::Rc;
+use super::file_system::{FileSystem, AssetSource};
+use crate::rendering::TextureAtlas;
+use super::localization::StringTable;
 
 pub type AssetID = String;
 
@@ -11,6 +17,14 @@ pub type AssetID = String;
 pub enum AssetType {
     Texture = 0,
     Font,
+    SoundBuffer,
+    Music,
+    Shader,
+    Image,
+    Data,
+    Text,
+    Atlas,
+    Strings,
 
     Count
 }
@@ -20,36 +34,186 @@ impl Into<usize> for AssetType {
         self as usize
     }
 }
+
+/// Music is streamed by SFML while it plays, so the asset keeps
+/// the file content and a new `Music` is opened from it when needed.
+pub struct MusicSource {
+    data: Vec<u8>
+}
+
+impl MusicSource {
+    fn new(data: Vec<u8>) -> Self {
+        Self {
+            data
+        }
+    }
+
+    pub fn open(&self) -> Option<Music<'_>> {
+        Music::from_memory(&self.data)
+    }
+}
+
+/// Font and the file content it is read from
+///
+/// SFML reads the font data lazily, so the content is kept with the font. Clones share
+/// it and stay valid when the asset is unloaded.
+#[derive(Clone)]
+pub struct FontAsset {
+    // Dropped before the data it reads
+    font: SfBox<Font>,
+    _data: Rc<[u8]>
+}
+
+impl FontAsset {
+    pub fn from_memory(data: impl Into<Rc<[u8]>>) -> Option<Self> {
+        let data = data.into();
+        // The data is owned by the asset and outlives the font
+        let font = unsafe { Font::from_memory(&data) }?;
+        Some(Self {
+            font,
+            _data: data
+        })
+    }
+}
+
+impl Deref for FontAsset {
+    type Target = Font;
+
+    fn deref(&self) -> &Font {
+        &self.font
+    }
+}
+
+fn load_texture(file_system: &FileSystem, path: &str) -> Result<RcTexture, String> {
+    let data = file_system.read(path)?;
+    let mut texture = RcTexture::new().ok_or("Cannot create texture")?;
+    texture.load_from_memory(&data, IntRect::default()).map_err(|_| format!("Invalid texture file {}", path))?;
+    Ok(texture)
+}
+
+fn load_font(file_system: &FileSystem, path: &str) -> Result<FontAsset, String> {
+    FontAsset::from_memory(file_system.read(path)?).ok_or(format!("Invalid font file {}", path))
+}
+
+fn load_music(file_system: &FileSystem, path: &str) -> Result<MusicSource, String> {
+    let music = MusicSource::new(file_system.read(path)?);
+    if music.open().is_none() {
+        return Err(format!("Invalid music file {}", path));
+    }
+    Ok(music)
+}
+
+fn load_shader(file_system: &FileSystem, path: &str) -> Result<Shader<'static>, String> {
+    // A shader asset is identified by its path without extension,
+    // the stages are found next to it as `<path>.vert` and `<path>.frag`
+    let vert = file_system.read_to_string(&format!("{}.vert", path)).ok();
+    let frag = file_system.read_to_string(&format!("{}.frag", path)).ok();
+
+    let shader = match (vert, frag) {
+        (Some(vert), Some(frag)) => Shader::from_memory_vert_frag(&vert, &frag),
+        (Some(vert), None) => Shader::from_memory(&vert, ShaderType::Vertex),
+        (None, Some(frag)) => Shader::from_memory(&frag, ShaderType::Fragment),
+        (None, None) => return Err(format!("No shader stage found for {}", path))
+    };
+
+    shader.map_err(|_| format!("Cannot compile shader {}", path))
+}
+
+//////////////// Fallbacks ////////////////////////
+
+const FALLBACK_FONT: &[u8] = include_bytes!("../../assets/fonts/slkscr.ttf");
+
+/// Magenta and black checkerboard, hard to miss on screen
+pub fn checkerboard_image() -> Image {
+    let size = 16;
+    let mut pixels = Vec::with_capacity(size * size * 4);
+    for y in 0..size {
+        for x in 0..size {
+            let color = if (x / 8 + y / 8) % 2 == 0 { Color::MAGENTA } else { Color::BLACK };
+            pixels.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+    }
+
+    unsafe { Image::create_from_pixels(size as u32, size as u32, &pixels) }.unwrap()
+}
+
+fn fallback_texture() -> RcTexture {
+    let mut texture = RcTexture::new().unwrap();
+    texture.load_from_image(&checkerboard_image(), IntRect::default()).unwrap();
+    texture.set_repeated(true);
+    texture
+}
+
+pub(crate) fn fallback_font() -> FontAsset {
+    FontAsset::from_memory(FALLBACK_FONT).unwrap()
+}
+
+pub struct AssetLoadError {
+    pub asset_type: usize,
+    pub id: String,
+    pub path: String,
+    pub error: String,
+    pub fallback_used: bool
+}
+
+impl Display for AssetLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Cannot load asset {} of type {} from {}: {}", self.id, self.asset_type, self.path, self.error)?;
+        if self.fallback_used {
+            write!(f, " (fallback used)")?;
+        }
+        Ok(())
+    }
+}
 ///////////////////////////////////////////////////
 
 pub trait AssetsGroup {
     type AssetsId: Debug + Eq + Hash;
     fn has_next_load(&self) -> bool;
     fn get_next_load(&mut self) -> (Self::AssetsId, String);
-    fn load(&mut self, id: Self::AssetsId, path: &str);
+    fn load(&mut self, id: Self::AssetsId, path: &str, file_system: &FileSystem) -> Result<(), String>;
     fn load_buffered(&mut self, id: Self::AssetsId, path: &str);
     fn get_asset(&self, id: Self::AssetsId) -> Result<&dyn std::any::Any, String>;
+    /// Removes the asset, returns false if it is not loaded
+    fn unload(&mut self, id: Self::AssetsId) -> bool;
+
+    /// Stores the fallback asset for the id, returns false if the group has no fallback
+    fn load_fallback(&mut self, id: Self::AssetsId) -> bool;
+    /// `fallback` must be a `Box<dyn Fn() -> T>` where `T` is the group assets type
+    fn set_fallback(&mut self, fallback: Option<Box<dyn Any>>) -> Result<(), String>;
+}
+
+/// Group storing assets of a single type, which can be registered by type
+pub trait TypedAssetsGroup: AssetsGroup {
+    type Asset: 'static;
 }
 
-pub struct DefaultAssetsGroup<ID, T, F: Fn(&'static str) -> T> {
+pub struct DefaultAssetsGroup<ID, T, F: Fn(&FileSystem, &str) -> Result<T, String>> {
     loader: F,
+    fallback: Option<Box<dyn Fn() -> T>>,
     storage: HashMap<ID, T>,
     buffered_loading: VecDeque<(ID, AssetID)>,
 }
 
 /////////////// Textures Assets ////////////////////
 
-impl<ID, T, F: Fn(&str) -> T> DefaultAssetsGroup<ID, T, F> {
-    fn new(loader: F) -> Self {
+impl<ID, T, F: Fn(&FileSystem, &str) -> Result<T, String>> DefaultAssetsGroup<ID, T, F> {
+    pub fn new(loader: F) -> Self {
         Self {
             loader,
+            fallback: None,
             storage: HashMap::new(),
             buffered_loading: VecDeque::new()
         }
     }
+
+    pub fn with_fallback(mut self, fallback: impl Fn() -> T + 'static) -> Self {
+        self.fallback = Some(Box::new(fallback));
+        self
+    }
 }
 
-impl<ID: Debug + Eq + Hash + Clone, T: 'static, F: Fn(&str) -> T> AssetsGroup for DefaultAssetsGroup<ID, T, F> {
+impl<ID: Debug + Eq + Hash + Clone, T: 'static, F: Fn(&FileSystem, &str) -> Result<T, String>> AssetsGroup for DefaultAssetsGroup<ID, T, F> {
     type AssetsId = ID;    
 
     fn has_next_load(&self) -> bool {
@@ -70,20 +234,49 @@ impl<ID: Debug + Eq + Hash + Clone, T: 'static, F: Fn(&str) -> T> AssetsGroup fo
         }
     }
 
-    fn load(&mut self, id: Self::AssetsId, path: &str) {
-        let asset = (self.loader)(path);
-
+    fn load(&mut self, id: Self::AssetsId, path: &str, file_system: &FileSystem) -> Result<(), String> {
         if self.storage.contains_key(&id) {
             panic!("Asset id {:?} already exists", id);
         }
+
+        let asset = (self.loader)(file_system, path)?;
         self.storage.insert(id, asset);
+        Ok(())
     }
 
     fn load_buffered(&mut self, id: Self::AssetsId, path: &str) {
         self.buffered_loading.push_back((id, path.to_string()))
     }
 
+    fn unload(&mut self, id: Self::AssetsId) -> bool {
+        self.storage.remove(&id).is_some()
+    }
+
+    fn load_fallback(&mut self, id: Self::AssetsId) -> bool {
+        match &self.fallback {
+            Some(fallback) => {
+                self.storage.insert(id, fallback());
+                true
+            }
+            None => false
+        }
+    }
+
+    fn set_fallback(&mut self, fallback: Option<Box<dyn Any>>) -> Result<(), String> {
+        self.fallback = match fallback {
+            Some(fallback) => match fallback.downcast::<Box<dyn Fn() -> T>>() {
+                Ok(fallback) => Some(*fallback),
+                Err(_) => return Err("Fallback have incompatible type".to_string())
+            },
+            None => None
+        };
+        Ok(())
+    }
+
+}
 
+impl<ID: Debug + Eq + Hash + Clone, T: 'static, F: Fn(&FileSystem, &str) -> Result<T, String>> TypedAssetsGroup for DefaultAssetsGroup<ID, T, F> {
+    type Asset = T;
 }
 
 //////////////////////////////////////
@@ -91,6 +284,11 @@ impl<ID: Debug + Eq + Hash + Clone, T: 'static, F: Fn(&str) -> T> AssetsGroup fo
 pub struct AssetsManager<T> {
     //textures: TexturesAssets,
     assets_groups: Vec<Box<dyn AssetsGroup<AssetsId = T>>>,
+    file_system: FileSystem,
+    types_indices: HashMap<TypeId, usize>,
+    extensions_indices: HashMap<String, usize>,
+    load_errors: Vec<AssetLoadError>,
+    strict: bool,
     total_loadings: usize,
     remaining_loadings: usize
 }
@@ -100,8 +298,32 @@ pub type DefaultAssetsManager = AssetsManager<AssetID>;
 impl DefaultAssetsManager {
     pub fn default() -> Self {
         let mut res = AssetsManager::new();
-        res.register_assets_group(Box::new(DefaultAssetsGroup::new(|p| RcTexture::from_file(p).unwrap())));
-        res.register_assets_group(Box::new(DefaultAssetsGroup::new(|p| Font::from_file(p).unwrap())));
+        res.register_typed_group::<RcTexture>(
+            Box::new(DefaultAssetsGroup::new(load_texture).with_fallback(fallback_texture)),
+            &["png", "jpg", "jpeg", "bmp", "tga", "gif", "psd", "hdr", "pic"]
+        );
+        res.register_typed_group::<FontAsset>(
+            Box::new(DefaultAssetsGroup::new(load_font).with_fallback(fallback_font)),
+            &["ttf", "otf"]
+        );
+        res.register_typed_group::<SfBox<SoundBuffer>>(
+            Box::new(DefaultAssetsGroup::new(|fs, p| {
+                SoundBuffer::from_memory(&fs.read(p)?).map_err(|_| format!("Invalid sound file {}", p))
+            })),
+            &["wav", "ogg", "flac"]
+        );
+        res.register_typed_group::<MusicSource>(Box::new(DefaultAssetsGroup::new(load_music)), &[]);
+        res.register_typed_group::<Shader<'static>>(Box::new(DefaultAssetsGroup::new(load_shader)), &[]);
+        res.register_typed_group::<Image>(
+            Box::new(DefaultAssetsGroup::new(|fs, p| {
+                Image::from_memory(&fs.read(p)?).ok_or(format!("Invalid image file {}", p))
+            }).with_fallback(checkerboard_image)),
+            &[]
+        );
+        res.register_typed_group::<Vec<u8>>(Box::new(DefaultAssetsGroup::new(|fs, p| fs.read(p))), &["bin"]);
+        res.register_typed_group::<String>(Box::new(DefaultAssetsGroup::new(|fs, p| fs.read_to_string(p))), &["txt", "json"]);
+        res.register_typed_group::<TextureAtlas>(Box::new(DefaultAssetsGroup::new(TextureAtlas::load)), &["atlas"]);
+        res.register_typed_group::<StringTable>(Box::new(DefaultAssetsGroup::new(StringTable::load)), &["lang"]);
         res
     }
 }
@@ -111,6 +333,11 @@ impl<T: Debug + Eq + Hash + Clone> AssetsManager<T> {
     pub fn new() -> Self {
         Self {
             assets_groups: Vec::new(),
+            file_system: FileSystem::with_working_directory(),
+            types_indices: HashMap::new(),
+            extensions_indices: HashMap::new(),
+            load_errors: Vec::new(),
+            strict: false,
             total_loadings: 0,
             remaining_loadings: 0
         }
@@ -122,8 +349,147 @@ impl<T: Debug + Eq + Hash + Clone> AssetsManager<T> {
         );
     }
 
+    /// Registers a group storing assets of type `A`, they can then be used with
+    /// `load`, `get` and the files with the given extensions with `load_by_extension`
+    pub fn register_typed_group<A: 'static>(
+        &mut self,
+        assets_group: Box<impl TypedAssetsGroup<AssetsId = T, Asset = A> + 'static>,
+        extensions: &[&str])
+    {
+        if self.types_indices.contains_key(&TypeId::of::<A>()) {
+            panic!("Assets type {} is already registered", std::any::type_name::<A>());
+        }
+        let index = self.assets_groups.len();
+        self.types_indices.insert(TypeId::of::<A>(), index);
+
+        for extension in extensions {
+            self.extensions_indices.insert(extension.to_lowercase(), index);
+        }
+
+        self.register_assets_group(assets_group);
+    }
+
+    pub fn register_loader<A: 'static>(
+        &mut self,
+        extensions: &[&str],
+        loader: impl Fn(&FileSystem, &str) -> Result<A, String> + 'static)
+    where
+        T: 'static
+    {
+        self.register_typed_group::<A>(Box::new(DefaultAssetsGroup::new(loader)), extensions);
+    }
+
+    /// Index of the group storing assets of type `A`, usable where an asset type is expected
+    pub fn type_index<A: 'static>(&self) -> Option<usize> {
+        self.types_indices.get(&TypeId::of::<A>()).copied()
+    }
+
+    pub(crate) fn typed_index<A: 'static>(&self) -> usize {
+        self.type_index::<A>()
+            .unwrap_or_else(|| panic!("Assets type {} is not registered", std::any::type_name::<A>()))
+    }
+
+    pub(crate) fn extension_index(&self, path: &str) -> usize {
+        let extension = std::path::Path::new(path).extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        *self.extensions_indices.get(&extension)
+            .unwrap_or_else(|| panic!("No assets type registered for the file {}", path))
+    }
+
+    pub fn load<A: 'static>(&mut self, id: T, path: &str) {
+        self.load_asset(self.typed_index::<A>(), id, path);
+    }
+
+    pub fn load_buffered<A: 'static>(&mut self, id: T, path: &str) {
+        self.load_asset_buffered(self.typed_index::<A>(), id, path);
+    }
+
+    pub fn get<A: 'static>(&self, id: T) -> Result<&A, String> {
+        let index = self.type_index::<A>()
+            .ok_or(format!("Assets type {} is not registered", std::any::type_name::<A>()))?;
+        self.get_asset(index, id)
+    }
+
+    pub fn unload<A: 'static>(&mut self, id: T) -> bool {
+        self.unload_asset(self.typed_index::<A>(), id)
+    }
+
+    /// Loads the file with the group registered for its extension
+    pub fn load_by_extension(&mut self, id: T, path: &str) {
+        self.load_asset(self.extension_index(path), id, path);
+    }
+
+    pub fn load_by_extension_buffered(&mut self, id: T, path: &str) {
+        self.load_asset_buffered(self.extension_index(path), id, path);
+    }
+
+    /// Mounts a new source, it has priority over the already mounted ones
+    pub fn mount(&mut self, source: impl AssetSource + 'static) {
+        self.file_system.mount(source);
+    }
+
+    pub fn file_system(&self) -> &FileSystem {
+        &self.file_system
+    }
+
+    pub fn file_system_mut(&mut self) -> &mut FileSystem {
+        &mut self.file_system
+    }
+
+    /// In strict mode a failed loading does not use the fallback asset,
+    /// the asset is missing and the error is in `load_errors`
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Replaces the asset used when a loading of type `A` fails
+    pub fn set_fallback<A: 'static>(&mut self, fallback: impl Fn() -> A + 'static) -> Result<(), String> {
+        let fallback: Box<dyn Fn() -> A> = Box::new(fallback);
+        self.typed_group::<A>()?.set_fallback(Some(Box::new(fallback)))
+    }
+
+    pub fn remove_fallback<A: 'static>(&mut self) -> Result<(), String> {
+        self.typed_group::<A>()?.set_fallback(None)
+    }
+
+    fn typed_group<A: 'static>(&mut self) -> Result<&mut Box<dyn AssetsGroup<AssetsId = T>>, String> {
+        let index = self.type_index::<A>()
+            .ok_or(format!("Assets type {} is not registered", std::any::type_name::<A>()))?;
+        Ok(&mut self.assets_groups[index])
+    }
+
+    /// Loadings that failed since the creation of the manager
+    pub fn load_errors(&self) -> &[AssetLoadError] {
+        &self.load_errors
+    }
+
+    pub fn load_report(&self) -> String {
+        self.load_errors.iter()
+            .map(|error| error.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
     pub fn load_asset<AT: Into<usize>>(&mut self, asset_type: AT, id: T, path: &str) {
-        self.assets_groups[asset_type.into()].load(id, path);
+        let asset_type = asset_type.into();
+        let group = &mut self.assets_groups[asset_type];
+
+        if let Err(error) = group.load(id.clone(), path, &self.file_system) {
+            let fallback_used = !self.strict && group.load_fallback(id.clone());
+            self.load_errors.push(AssetLoadError {
+                asset_type,
+                id: format!("{:?}", id),
+                path: path.to_string(),
+                error,
+                fallback_used
+            });
+        }
     }
 
     pub fn load_asset_buffered<AT: Into<usize>>(&mut self, asset_type: AT, id: T, path: &str) {
@@ -131,6 +497,10 @@ impl<T: Debug + Eq + Hash + Clone> AssetsManager<T> {
         self.add_load_buffer();
     }
 
+    pub fn unload_asset<AT: Into<usize>>(&mut self, asset_type: AT, id: T) -> bool {
+        self.assets_groups[asset_type.into()].unload(id)
+    }
+
     pub fn get_asset<A: 'static, AT: Into<usize>>(&self, asset_type: AT, id: T) -> Result<&A, String> {
         let any_value = self.assets_groups[asset_type.into()].get_asset(id)?;
 
@@ -168,8 +538,62 @@ impl<T: Debug + Eq + Hash + Clone> AssetsManager<T> {
         self.remaining_loadings -= 1;
     }
 
+    /// True while buffered loadings remain to be launched
+    pub fn is_loading(&self) -> bool {
+        self.remaining_loadings > 0
+    }
+
     pub fn loading_percentage(&self) -> f32 {
+        if self.total_loadings == 0 {
+            return 100.0;
+        }
         let done = self.total_loadings - self.remaining_loadings;
         done as f32 / self.total_loadings as f32 * 100.0
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::file_system::EmbeddedSource;
+
+    fn manager() -> AssetsManager<AssetID> {
+        let mut res = AssetsManager::new();
+        res.file_system_mut().unmount_all();
+        res.mount(EmbeddedSource::new().with_file("hello.txt", b"hello"));
+        res.register_loader::<String>(&["txt"], |fs, p| fs.read_to_string(p));
+        res
+    }
+
+    #[test]
+    fn failed_loading_uses_the_fallback() {
+        let mut manager = manager();
+        manager.set_fallback::<String>(|| "fallback".to_string()).unwrap();
+
+        manager.load::<String>("hello".to_string(), "hello.txt");
+        manager.load::<String>("missing".to_string(), "missing.txt");
+        assert_eq!(manager.get::<String>("hello".to_string()).unwrap(), "hello");
+        assert_eq!(manager.get::<String>("missing".to_string()).unwrap(), "fallback");
+        assert_eq!(manager.load_errors().len(), 1);
+        assert!(manager.load_errors()[0].fallback_used);
+    }
+
+    #[test]
+    fn strict_mode_records_the_error_without_fallback() {
+        let mut manager = manager();
+        manager.set_fallback::<String>(|| "fallback".to_string()).unwrap();
+        manager.set_strict(true);
+
+        manager.load::<String>("missing".to_string(), "missing.txt");
+        assert!(manager.get::<String>("missing".to_string()).is_err());
+        assert_eq!(manager.load_errors().len(), 1);
+        assert!(!manager.load_errors()[0].fallback_used);
+    }
+
+    #[test]
+    fn fallback_of_an_unregistered_type_is_an_error() {
+        let mut manager = manager();
+        assert!(manager.set_fallback::<Vec<u8>>(Vec::new).is_err());
+        assert!(manager.remove_fallback::<Vec<u8>>().is_err());
+        assert!(manager.remove_fallback::<String>().is_ok());
+    }
+}
@@ -0,0 +1,464 @@
+use std::collections::HashMap;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::io::{self, Cursor, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use zip::ZipArchive;
+
+/// Somewhere assets can be read from, mounted in a `FileSystem`
+pub trait AssetSource {
+    fn read(&self, path: &str) -> Option<Vec<u8>>;
+
+    fn exists(&self, path: &str) -> bool {
+        self.read(path).is_some()
+    }
+}
+
+fn normalize_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut path = path.as_str();
+    while let Some(stripped) = path.strip_prefix("./") {
+        path = stripped;
+    }
+    path.trim_start_matches('/').to_string()
+}
+
+//////////////// Directory source ////////////////////////
+
+pub struct DirectorySource {
+    root: PathBuf
+}
+
+impl DirectorySource {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into()
+        }
+    }
+
+    /// Directory of the running executable, where a shipped build keeps its assets
+    pub fn next_to_executable() -> io::Result<Self> {
+        let executable = std::env::current_exe()?;
+        let directory = executable.parent()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "The executable has no directory"))?;
+        Ok(Self::new(directory))
+    }
+
+    /// Path of the file under the root, none if the path would leave it
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let path = normalize_path(path);
+        let path = Path::new(&path);
+        if !path.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir)) {
+            return None;
+        }
+        Some(self.root.join(path))
+    }
+}
+
+impl AssetSource for DirectorySource {
+    fn read(&self, path: &str) -> Option<Vec<u8>> {
+        std::fs::read(self.resolve(path)?).ok()
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.resolve(path).is_some_and(|path| path.is_file())
+    }
+}
+
+//////////////// Embedded source ////////////////////////
+
+/// Files compiled into the binary with `include_bytes!`
+#[derive(Default)]
+pub struct EmbeddedSource {
+    files: HashMap<String, &'static [u8]>
+}
+
+impl EmbeddedSource {
+    pub fn new() -> Self {
+        Self {
+            files: HashMap::new()
+        }
+    }
+
+    pub fn with_file(mut self, path: &str, content: &'static [u8]) -> Self {
+        self.add_file(path, content);
+        self
+    }
+
+    pub fn add_file(&mut self, path: &str, content: &'static [u8]) {
+        self.files.insert(normalize_path(path), content);
+    }
+}
+
+impl AssetSource for EmbeddedSource {
+    fn read(&self, path: &str) -> Option<Vec<u8>> {
+        self.files.get(&normalize_path(path)).map(|content| content.to_vec())
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.files.contains_key(&normalize_path(path))
+    }
+}
+
+//////////////// Pak archive source ////////////////////////
+
+const PAK_MAGIC: &[u8; 8] = b"SFMXPAK\0";
+
+/// A simple uncompressed archive
+///
+/// Unlike a zip archive, reading a file is a copy of a slice of the archive without
+/// decompression, which suits archives embedded in the binary with `from_static`.
+///
+/// Layout (little endian):
+/// - magic `SFMXPAK\0`
+/// - entries count: u32
+/// - for each entry: name length u16, name (utf8), offset u64, size u64
+/// - files content, offsets are relative to the start of the archive
+///
+/// The files must be after the entries and must not overlap.
+pub struct PakSource {
+    data: Cow<'static, [u8]>,
+    entries: HashMap<String, (usize, usize)>
+}
+
+impl PakSource {
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_data(Cow::Owned(std::fs::read(path)?))
+    }
+
+    pub fn from_memory(data: Vec<u8>) -> io::Result<Self> {
+        Self::from_data(Cow::Owned(data))
+    }
+
+    /// Archive embedded in the binary with `include_bytes!`
+    pub fn from_static(data: &'static [u8]) -> io::Result<Self> {
+        Self::from_data(Cow::Borrowed(data))
+    }
+
+    fn from_data(data: Cow<'static, [u8]>) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Invalid pak archive");
+
+        let mut cursor = 0;
+        let mut take = |count: usize| -> io::Result<&[u8]> {
+            let bytes = data.get(cursor..cursor + count).ok_or_else(invalid)?;
+            cursor += count;
+            Ok(bytes)
+        };
+
+        if take(PAK_MAGIC.len())? != PAK_MAGIC {
+            return Err(invalid());
+        }
+
+        let count = u32::from_le_bytes(take(4)?.try_into().unwrap());
+        let mut entries = HashMap::new();
+        for _ in 0..count {
+            let name_len = u16::from_le_bytes(take(2)?.try_into().unwrap()) as usize;
+            let name = String::from_utf8(take(name_len)?.to_vec()).map_err(|_| invalid())?;
+            let offset = u64::from_le_bytes(take(8)?.try_into().unwrap()) as usize;
+            let size = u64::from_le_bytes(take(8)?.try_into().unwrap()) as usize;
+
+            if entries.insert(normalize_path(&name), (offset, size)).is_some() {
+                return Err(invalid());
+            }
+        }
+
+        let mut ranges = entries.values().copied().collect::<Vec<_>>();
+        ranges.sort_unstable();
+        let mut files_start = cursor;
+        for (offset, size) in ranges {
+            let end = offset.checked_add(size).ok_or_else(invalid)?;
+            if offset < files_start || end > data.len() {
+                return Err(invalid());
+            }
+            files_start = end;
+        }
+
+        Ok(Self {
+            data,
+            entries
+        })
+    }
+
+    /// Packs every file under `root` into an archive, paths are stored relative to `root`
+    pub fn pack_directory(root: impl AsRef<Path>, writer: &mut impl Write) -> io::Result<()> {
+        let root = root.as_ref();
+        let mut files = Vec::new();
+        collect_files(root, root, &mut files)?;
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Self::pack(&files, writer)
+    }
+
+    pub fn pack(files: &[(String, Vec<u8>)], writer: &mut impl Write) -> io::Result<()> {
+        let header_size = PAK_MAGIC.len() + 4 + files.iter()
+            .map(|(name, _)| 2 + name.len() + 8 + 8)
+            .sum::<usize>();
+
+        writer.write_all(PAK_MAGIC)?;
+        writer.write_all(&(files.len() as u32).to_le_bytes())?;
+
+        let mut offset = header_size;
+        for (name, content) in files {
+            writer.write_all(&(name.len() as u16).to_le_bytes())?;
+            writer.write_all(name.as_bytes())?;
+            writer.write_all(&(offset as u64).to_le_bytes())?;
+            writer.write_all(&(content.len() as u64).to_le_bytes())?;
+            offset += content.len();
+        }
+
+        for (_, content) in files {
+            writer.write_all(content)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<(String, Vec<u8>)>) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        }
+        else {
+            let name = path.strip_prefix(root).unwrap().to_string_lossy();
+            files.push((normalize_path(&name), std::fs::read(&path)?));
+        }
+    }
+    Ok(())
+}
+
+impl AssetSource for PakSource {
+    fn read(&self, path: &str) -> Option<Vec<u8>> {
+        let (offset, size) = *self.entries.get(&normalize_path(path))?;
+        Some(self.data[offset..offset + size].to_vec())
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.entries.contains_key(&normalize_path(path))
+    }
+}
+
+//////////////// Zip archive source ////////////////////////
+
+/// Zip archive, stored or deflate compressed
+///
+/// The files are decompressed each time they are read.
+pub struct ZipSource {
+    archive: RefCell<ZipArchive<Cursor<Cow<'static, [u8]>>>>,
+    /// Normalized paths and the names in the archive
+    entries: HashMap<String, String>
+}
+
+impl ZipSource {
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_data(Cow::Owned(std::fs::read(path)?))
+    }
+
+    pub fn from_memory(data: Vec<u8>) -> io::Result<Self> {
+        Self::from_data(Cow::Owned(data))
+    }
+
+    /// Archive embedded in the binary with `include_bytes!`
+    pub fn from_static(data: &'static [u8]) -> io::Result<Self> {
+        Self::from_data(Cow::Borrowed(data))
+    }
+
+    fn from_data(data: Cow<'static, [u8]>) -> io::Result<Self> {
+        let archive = ZipArchive::new(Cursor::new(data))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid zip archive: {}", e)))?;
+
+        let entries = archive.file_names()
+            .filter(|name| !name.ends_with('/'))
+            .map(|name| (normalize_path(name), name.to_string()))
+            .collect();
+
+        Ok(Self {
+            archive: RefCell::new(archive),
+            entries
+        })
+    }
+}
+
+impl AssetSource for ZipSource {
+    fn read(&self, path: &str) -> Option<Vec<u8>> {
+        let name = self.entries.get(&normalize_path(path))?;
+        let mut archive = self.archive.borrow_mut();
+        let mut file = archive.by_name(name).ok()?;
+
+        let mut content = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut content).ok()?;
+        Some(content)
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.entries.contains_key(&normalize_path(path))
+    }
+}
+
+//////////////////////////////////////
+
+/// Virtual file system used by the assets manager
+///
+/// Sources are searched from the last mounted to the first one,
+/// so a source can override the files of the previous ones.
+#[derive(Default)]
+pub struct FileSystem {
+    sources: Vec<Box<dyn AssetSource>>
+}
+
+impl FileSystem {
+    pub fn new() -> Self {
+        Self {
+            sources: Vec::new()
+        }
+    }
+
+    /// File system reading from the working directory
+    pub fn with_working_directory() -> Self {
+        let mut res = Self::new();
+        res.mount(DirectorySource::new("."));
+        res
+    }
+
+    pub fn mount(&mut self, source: impl AssetSource + 'static) {
+        self.sources.push(Box::new(source));
+    }
+
+    pub fn unmount_all(&mut self) {
+        self.sources.clear();
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        self.sources.iter().rev().any(|source| source.exists(path))
+    }
+
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, String> {
+        self.sources.iter()
+            .rev()
+            .find_map(|source| source.read(path))
+            .ok_or_else(|| format!("File {} is not found in any mounted source", path))
+    }
+
+    pub fn read_to_string(&self, path: &str) -> Result<String, String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|_| format!("File {} is not valid utf-8", path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pak(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let files = files.iter().map(|(name, content)| (name.to_string(), content.to_vec())).collect::<Vec<_>>();
+        let mut data = Vec::new();
+        PakSource::pack(&files, &mut data).unwrap();
+        data
+    }
+
+    /// Header of a pak with the entries, followed by `content`
+    fn raw_pak(entries: &[(&str, u64, u64)], content: &[u8]) -> Vec<u8> {
+        let mut data = PAK_MAGIC.to_vec();
+        data.extend((entries.len() as u32).to_le_bytes());
+        for (name, offset, size) in entries {
+            data.extend((name.len() as u16).to_le_bytes());
+            data.extend(name.as_bytes());
+            data.extend(offset.to_le_bytes());
+            data.extend(size.to_le_bytes());
+        }
+        data.extend(content);
+        data
+    }
+
+    #[test]
+    fn directory_paths_stay_under_the_root() {
+        let source = DirectorySource::new("root");
+        assert_eq!(source.resolve("a/b.png"), Some(PathBuf::from("root/a/b.png")));
+        assert_eq!(source.resolve("./a/./b.png"), Some(PathBuf::from("root/a/b.png")));
+        assert_eq!(source.resolve("a\\b.png"), Some(PathBuf::from("root/a/b.png")));
+        // Absolute paths are taken from the root
+        assert_eq!(source.resolve("/a/b.png"), Some(PathBuf::from("root/a/b.png")));
+
+        assert_eq!(source.resolve("../secret"), None);
+        assert_eq!(source.resolve("a/../../secret"), None);
+        assert_eq!(source.resolve("a/../b.png"), None);
+        assert_eq!(source.resolve("a\\..\\..\\secret"), None);
+        assert_eq!(source.resolve("//../secret"), None);
+    }
+
+    #[test]
+    fn pak_reads_its_entries() {
+        let source = PakSource::from_memory(pak(&[("a.txt", b"first"), ("dir/b.txt", b"second"), ("empty", b"")])).unwrap();
+        assert_eq!(source.read("a.txt").as_deref(), Some(&b"first"[..]));
+        assert_eq!(source.read("./dir/b.txt").as_deref(), Some(&b"second"[..]));
+        assert_eq!(source.read("dir\\b.txt").as_deref(), Some(&b"second"[..]));
+        assert_eq!(source.read("empty").as_deref(), Some(&b""[..]));
+        assert!(source.exists("dir/b.txt"));
+        assert!(!source.exists("dir"));
+        assert_eq!(source.read("missing"), None);
+    }
+
+    #[test]
+    fn pak_rejects_invalid_entries() {
+        let data = pak(&[("a.txt", b"first"), ("b.txt", b"second")]);
+        assert!(PakSource::from_memory(data.clone()).is_ok());
+        assert!(PakSource::from_memory(data[..data.len() - 1].to_vec()).is_err());
+        assert!(PakSource::from_memory(data[..PAK_MAGIC.len() + 6].to_vec()).is_err());
+        assert!(PakSource::from_memory(b"NOTAPAK\0\0\0\0\0".to_vec()).is_err());
+
+        let header = raw_pak(&[("a", 0, 0), ("b", 0, 0)], b"").len() as u64;
+        let valid = raw_pak(&[("a", header, 2), ("b", header + 2, 2)], b"abcd");
+        assert!(PakSource::from_memory(valid).is_ok());
+        // Past the end
+        assert!(PakSource::from_memory(raw_pak(&[("a", header, 2), ("b", header + 2, 3)], b"abcd")).is_err());
+        assert!(PakSource::from_memory(raw_pak(&[("a", header, 2), ("b", u64::MAX, 2)], b"abcd")).is_err());
+        assert!(PakSource::from_memory(raw_pak(&[("a", header, 2), ("b", 2, u64::MAX)], b"abcd")).is_err());
+        // Overlapping each other or the entries
+        assert!(PakSource::from_memory(raw_pak(&[("a", header, 3), ("b", header + 2, 2)], b"abcd")).is_err());
+        assert!(PakSource::from_memory(raw_pak(&[("a", header - 1, 2), ("b", header + 2, 2)], b"abcd")).is_err());
+        // Same name twice
+        assert!(PakSource::from_memory(raw_pak(&[("a", header, 2), ("./a", header + 2, 2)], b"abcd")).is_err());
+    }
+
+    #[test]
+    fn zip_lookups() {
+        use zip::write::{SimpleFileOptions, ZipWriter};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        writer.add_directory("dir/", options).unwrap();
+        writer.start_file("dir/a.txt", options).unwrap();
+        writer.write_all(b"compressed content").unwrap();
+        writer.start_file("b.txt", options.compression_method(zip::CompressionMethod::Stored)).unwrap();
+        writer.write_all(b"stored").unwrap();
+        let data = writer.finish().unwrap().into_inner();
+
+        let source = ZipSource::from_memory(data).unwrap();
+        assert_eq!(source.read("dir/a.txt").as_deref(), Some(&b"compressed content"[..]));
+        assert_eq!(source.read("./dir/a.txt").as_deref(), Some(&b"compressed content"[..]));
+        assert_eq!(source.read("dir\\a.txt").as_deref(), Some(&b"compressed content"[..]));
+        assert_eq!(source.read("b.txt").as_deref(), Some(&b"stored"[..]));
+        assert!(!source.exists("dir"));
+        assert!(!source.exists("dir/"));
+        assert_eq!(source.read("missing.txt"), None);
+
+        assert!(ZipSource::from_memory(b"not a zip".to_vec()).is_err());
+    }
+
+    #[test]
+    fn last_mounted_source_is_read_first() {
+        let mut file_system = FileSystem::new();
+        file_system.mount(EmbeddedSource::new().with_file("a.txt", b"base").with_file("b.txt", b"base"));
+        file_system.mount(EmbeddedSource::new().with_file("a.txt", b"override"));
+
+        assert_eq!(file_system.read("a.txt").unwrap(), b"override");
+        assert_eq!(file_system.read("b.txt").unwrap(), b"base");
+        assert!(file_system.exists("b.txt"));
+        assert!(!file_system.exists("c.txt"));
+        assert!(file_system.read("c.txt").is_err());
+
+        file_system.unmount_all();
+        assert!(!file_system.exists("a.txt"));
+    }
+}
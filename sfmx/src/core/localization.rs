@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use toml::{Table, Value};
+use super::file_system::FileSystem;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PluralCategory {
+    Zero,
+    One,
+    Two,
+    Few,
+    Many,
+    Other
+}
+
+impl PluralCategory {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "zero" => Some(Self::Zero),
+            "one" => Some(Self::One),
+            "two" => Some(Self::Two),
+            "few" => Some(Self::Few),
+            "many" => Some(Self::Many),
+            "other" => Some(Self::Other),
+            _ => None
+        }
+    }
+}
+
+pub type PluralRule = fn(i64) -> PluralCategory;
+
+/// Plural rule of the languages without a rule set with `Localization::set_plural_rule`
+fn default_plural_rule(language: &str) -> PluralRule {
+    match language.split(['-', '_']).next().unwrap_or_default() {
+        "fr" | "pt" => |n| if n == 0 || n == 1 { PluralCategory::One } else { PluralCategory::Other },
+        "ja" | "zh" | "ko" | "vi" | "th" => |_| PluralCategory::Other,
+        _ => |n| if n == 1 { PluralCategory::One } else { PluralCategory::Other }
+    }
+}
+
+//////////////// String table ////////////////////////
+
+#[derive(Clone, Debug)]
+enum Message {
+    Simple(String),
+    Plural(HashMap<PluralCategory, String>)
+}
+
+/// Strings of a language, loaded from a toml file of `key = "value"` entries
+///
+/// Placeholders are written `{name}`, `{{` and `}}` are literal braces.
+/// A table with an `other` key is a plural message, its keys are the plural categories
+/// (`zero`, `one`, `two`, `few`, `many`, `other`), any other table is a section
+/// whose keys are prefixed with `section.`
+#[derive(Clone, Debug, Default)]
+pub struct StringTable {
+    messages: HashMap<String, Message>
+}
+
+impl StringTable {
+    pub fn new() -> Self {
+        Self {
+            messages: HashMap::new()
+        }
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let table = content.parse::<Table>().map_err(|e| format!("Invalid string table: {}", e))?;
+        let mut res = Self::new();
+        res.add_entries("", table)?;
+        Ok(res)
+    }
+
+    pub fn load(file_system: &FileSystem, path: &str) -> Result<Self, String> {
+        Self::parse(&file_system.read_to_string(path)?).map_err(|e| format!("{} in {}", e, path))
+    }
+
+    fn add_entries(&mut self, prefix: &str, table: Table) -> Result<(), String> {
+        for (key, value) in table {
+            let key = format!("{}{}", prefix, key);
+            match value {
+                Value::String(string) => {
+                    self.messages.insert(key, Message::Simple(string));
+                },
+                Value::Table(table) if table.contains_key("other") => {
+                    let mut forms = HashMap::new();
+                    for (category, form) in table {
+                        let category = PluralCategory::from_name(&category)
+                            .ok_or(format!("Unknown plural category {} for {}", category, key))?;
+                        let form = form.as_str().ok_or(format!("Plural form of {} is not a string", key))?;
+                        forms.insert(category, form.to_string());
+                    }
+                    self.messages.insert(key, Message::Plural(forms));
+                },
+                Value::Table(table) => self.add_entries(&format!("{}.", key), table)?,
+                _ => return Err(format!("Value of {} is not a string", key))
+            }
+        }
+        Ok(())
+    }
+
+    pub fn insert(&mut self, key: &str, value: &str) {
+        self.messages.insert(key.to_string(), Message::Simple(value.to_string()));
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.messages.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+//////////////// Text key ////////////////////////
+
+/// Key of a localized string with its arguments, used to bind texts to the localization
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextKey {
+    pub key: String,
+    pub args: Vec<(String, String)>,
+    /// Selects the plural form, also available as the `count` argument
+    pub count: Option<i64>
+}
+
+impl TextKey {
+    pub fn new(key: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            args: Vec::new(),
+            count: None
+        }
+    }
+
+    pub fn with_arg(mut self, name: &str, value: impl Display) -> Self {
+        self.args.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_count(mut self, count: i64) -> Self {
+        self.count = Some(count);
+        self
+    }
+}
+
+fn substitute(pattern: &str, args: &[(String, String)]) -> String {
+    let mut res = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                res.push('{');
+            },
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                res.push('}');
+            },
+            '{' => {
+                let mut name = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    name.push(c);
+                }
+                // An unclosed brace is kept as written
+                if !closed {
+                    res.push('{');
+                    res.push_str(&name);
+                    continue;
+                }
+                let name = name.trim();
+                match args.iter().find(|(arg, _)| arg == name) {
+                    Some((_, value)) => res.push_str(value),
+                    // Missing arguments are kept visible
+                    None => {
+                        res.push('{');
+                        res.push_str(name);
+                        res.push('}');
+                    }
+                }
+            },
+            _ => res.push(c)
+        }
+    }
+    res
+}
+
+//////////////////////////////////////
+
+/// String tables of the languages and the current language
+///
+/// A missing string is searched in the fallback language, then the key itself is returned.
+/// The revision changes with the language so the bound texts know when to refresh.
+#[derive(Default)]
+pub struct Localization {
+    tables: HashMap<String, StringTable>,
+    plural_rules: HashMap<String, PluralRule>,
+    language: String,
+    fallback_language: Option<String>,
+    revision: u32
+}
+
+impl Localization {
+    pub fn new() -> Self {
+        Self {
+            tables: HashMap::new(),
+            plural_rules: HashMap::new(),
+            language: String::new(),
+            fallback_language: None,
+            revision: 0
+        }
+    }
+
+    /// The first language added becomes the current and fallback one
+    pub fn add_language(&mut self, language: &str, table: StringTable) {
+        self.tables.insert(language.to_string(), table);
+        if self.language.is_empty() {
+            self.language = language.to_string();
+        }
+        if self.fallback_language.is_none() {
+            self.fallback_language = Some(language.to_string());
+        }
+        self.revision += 1;
+    }
+
+    pub fn load_language(&mut self, language: &str, file_system: &FileSystem, path: &str) -> Result<(), String> {
+        self.add_language(language, StringTable::load(file_system, path)?);
+        Ok(())
+    }
+
+    pub fn set_language(&mut self, language: &str) -> Result<(), String> {
+        if !self.tables.contains_key(language) {
+            return Err(format!("Language {} is not loaded", language));
+        }
+        if self.language != language {
+            self.language = language.to_string();
+            self.revision += 1;
+        }
+        Ok(())
+    }
+
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    pub fn languages(&self) -> Vec<&str> {
+        let mut languages = self.tables.keys().map(|language| language.as_str()).collect::<Vec<_>>();
+        languages.sort();
+        languages
+    }
+
+    pub fn set_fallback_language(&mut self, language: Option<&str>) {
+        self.fallback_language = language.map(|language| language.to_string());
+        self.revision += 1;
+    }
+
+    pub fn set_plural_rule(&mut self, language: &str, rule: PluralRule) {
+        self.plural_rules.insert(language.to_string(), rule);
+        self.revision += 1;
+    }
+
+    pub fn revision(&self) -> u32 {
+        self.revision
+    }
+
+    pub fn has_key(&self, key: &str) -> bool {
+        self.find(key).is_some()
+    }
+
+    pub fn tr(&self, key: &str) -> String {
+        self.format(&TextKey::new(key))
+    }
+
+    pub fn tr_with(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        let mut text_key = TextKey::new(key);
+        for (name, value) in args {
+            text_key = text_key.with_arg(name, value);
+        }
+        self.format(&text_key)
+    }
+
+    pub fn tr_plural(&self, key: &str, count: i64, args: &[(&str, &dyn Display)]) -> String {
+        let mut text_key = TextKey::new(key).with_count(count);
+        for (name, value) in args {
+            text_key = text_key.with_arg(name, value);
+        }
+        self.format(&text_key)
+    }
+
+    pub fn format(&self, text_key: &TextKey) -> String {
+        let (language, message) = match self.find(&text_key.key) {
+            Some(found) => found,
+            None => return text_key.key.clone()
+        };
+
+        let mut args = text_key.args.clone();
+        let pattern = match message {
+            Message::Simple(pattern) => pattern,
+            Message::Plural(forms) => {
+                let count = text_key.count.unwrap_or(1);
+                args.push(("count".to_string(), count.to_string()));
+
+                let rule = self.plural_rules.get(language).copied().unwrap_or_else(|| default_plural_rule(language));
+                let zero = if count == 0 { forms.get(&PluralCategory::Zero) } else { None };
+                zero.or_else(|| forms.get(&rule(count)))
+                    .unwrap_or(&forms[&PluralCategory::Other])
+            }
+        };
+        substitute(pattern, &args)
+    }
+
+    fn find(&self, key: &str) -> Option<(&str, &Message)> {
+        std::iter::once(self.language.as_str())
+            .chain(self.fallback_language.as_deref())
+            .find_map(|language| {
+                let message = self.tables.get(language)?.messages.get(key)?;
+                Some((language, message))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[(&str, &str)]) -> Vec<(String, String)> {
+        args.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn substitute_arguments() {
+        let args = args(&[("player", "X"), ("score", "3")]);
+        assert_eq!(substitute("{player} has { score } points", &args), "X has 3 points");
+        assert_eq!(substitute("{{player}} and }}{{", &args), "{player} and }{");
+        assert_eq!(substitute("{{{player}}}", &args), "{X}");
+    }
+
+    #[test]
+    fn substitute_missing_and_unclosed() {
+        let args = args(&[("player", "X")]);
+        assert_eq!(substitute("{winner} wins", &args), "{winner} wins");
+        assert_eq!(substitute("{player} and {player", &args), "X and {player");
+        assert_eq!(substitute("lone } brace", &args), "lone } brace");
+    }
+
+    fn localization(language: &str, content: &str) -> Localization {
+        let mut localization = Localization::new();
+        localization.add_language(language, StringTable::parse(content).unwrap());
+        localization
+    }
+
+    #[test]
+    fn plural_categories() {
+        let content = "[apples]\nzero = \"no apple\"\none = \"{count} apple\"\nother = \"{count} apples\"\n";
+        let mut localization = localization("en", content);
+        assert_eq!(localization.tr_plural("apples", 0, &[]), "no apple");
+        assert_eq!(localization.tr_plural("apples", 1, &[]), "1 apple");
+        assert_eq!(localization.tr_plural("apples", 5, &[]), "5 apples");
+
+        // Without a zero form, French uses one for 0 and 1
+        let content = "[apples]\none = \"{count} pomme\"\nother = \"{count} pommes\"\n";
+        localization.add_language("fr", StringTable::parse(content).unwrap());
+        localization.set_language("fr").unwrap();
+        assert_eq!(localization.tr_plural("apples", 0, &[]), "0 pomme");
+        assert_eq!(localization.tr_plural("apples", 2, &[]), "2 pommes");
+
+        localization.set_plural_rule("fr", |n| if n == 2 { PluralCategory::Two } else { PluralCategory::Other });
+        assert_eq!(localization.tr_plural("apples", 1, &[]), "1 pommes");
+        assert!(StringTable::parse("[apples]\nsome = \"x\"\nother = \"y\"\n").is_err());
+    }
+
+    #[test]
+    fn fallback_language() {
+        let mut localization = localization("en", "hello = \"Hello\"\nbye = \"Bye\"\n");
+        localization.add_language("fr", StringTable::parse("hello = \"Bonjour\"\n").unwrap());
+        localization.set_language("fr").unwrap();
+        assert_eq!(localization.tr("hello"), "Bonjour");
+        assert_eq!(localization.tr("bye"), "Bye");
+        assert_eq!(localization.tr("missing"), "missing");
+
+        localization.set_fallback_language(None);
+        assert_eq!(localization.tr("bye"), "bye");
+        assert!(localization.set_language("de").is_err());
+    }
+}
@@ -1,17 +1,33 @@
 #![allow(unused_variables)]
 
+use std::any::Any;
 use std::collections::{VecDeque, vec_deque::IterMut};
-use super::application::AppSignal;
 use crate::sfml_export::*;
 use super::assets_manager::{DefaultAssetsManager, AssetsManager};
 use crate::core::application::CursorSettings;
+use super::states_info::{StatesInfo, StateInfo, TransitionKind};
+use super::tasks::{Tasks, TaskOutput, NO_OWNER};
+use super::storage::Storage;
+use super::localization::Localization;
+use crate::rendering::frame_capture::FrameCapture;
+use crate::rendering::post_process::PostProcess;
 
 pub struct StateData<T> {
     pub data: T,
     pub delta_time: f32,
     pub assets_manager: DefaultAssetsManager,
     pub render_target_size: (u32, u32),
-    pub cursor: Option<CursorSettings>
+    pub cursor: Option<CursorSettings>,
+    /// Tasks spawned here belong to the state currently called
+    pub tasks: Tasks<T>,
+    /// Preferences and save files, in memory unless the application is built with a storage
+    pub storage: Storage,
+    pub localization: Localization,
+    /// Screenshots and recordings of the application window
+    pub capture: FrameCapture,
+    /// Passes applied to the whole frame, the states draw offscreen while one is enabled
+    pub post_process: PostProcess,
+    pub(crate) states_info: StatesInfo
 }
 
 impl<T> StateData<T> {
@@ -21,9 +37,24 @@ impl<T> StateData<T> {
             delta_time: 0.0,
             assets_manager: AssetsManager::default(),
             render_target_size: (0, 0),
-            cursor: None
+            cursor: None,
+            tasks: Tasks::new(),
+            storage: Storage::in_memory(),
+            localization: Localization::new(),
+            capture: FrameCapture::new(),
+            post_process: PostProcess::new(),
+            states_info: StatesInfo::new()
         }
     }
+
+    /// States stack of the application and its transitions history
+    pub fn states_info(&self) -> &StatesInfo {
+        &self.states_info
+    }
+
+    pub fn states_info_mut(&mut self) -> &mut StatesInfo {
+        &mut self.states_info
+    }
 }
 
 type StateRef<T> = Box<dyn State<T>>;
@@ -33,7 +64,42 @@ pub enum Transition<Data> {
     Add(StateRef<Data>),
     Replace(StateRef<Data>),
     Remove,
-    Quit
+    Quit,
+    /// Transition applied by the state machine of the state owning the current one
+    Parent(Box<Transition<Data>>)
+}
+
+/// Result of `State::handle_event`
+pub struct EventResponse<Data> {
+    pub transition: Transition<Data>,
+    /// An event that is not consumed is given to the state below
+    pub consumed: bool
+}
+
+impl<Data> EventResponse<Data> {
+    pub fn consumed(transition: Transition<Data>) -> Self {
+        Self {
+            transition,
+            consumed: true
+        }
+    }
+
+    pub fn ignored(transition: Transition<Data>) -> Self {
+        Self {
+            transition,
+            consumed: false
+        }
+    }
+}
+
+/// Answer of a state when a transition is about to leave or enter it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransitionGuard {
+    Allow,
+    /// The transition is dropped
+    Deny,
+    /// The transition is kept and checked again on the next frame
+    Defer
 }
 
 pub trait State<Data> {
@@ -41,15 +107,39 @@ pub trait State<Data> {
     fn on_end(&mut self, state_data: &mut StateData<Data>) {}
     
     fn on_event(&mut self, event: Event, state_data: &mut StateData<Data>) -> Transition<Data> { Transition::None }
+    /// Handles the event and reports if it was consumed, by default the event is given to `on_event` and consumed
+    fn handle_event(&mut self, event: Event, state_data: &mut StateData<Data>) -> EventResponse<Data> {
+        EventResponse::consumed(self.on_event(event, state_data))
+    }
+    /// A state returning false is skipped by the events not consumed by the states above it
+    fn receives_propagated_events(&self) -> bool { true }
     fn on_update(&mut self, state_data: &mut StateData<Data>) -> Transition<Data> { Transition::None }
     fn on_pause(&mut self, state_data: &mut StateData<Data>) {}
     fn on_resume(&mut self, state_data: &mut StateData<Data>) {}
     fn on_render(&mut self, state_data: &mut StateData<Data>, target: &mut dyn RenderTarget) -> bool {false}
+    /// An overlay is rendered over the state below it instead of replacing its rendering
+    fn is_overlay(&self) -> bool { false }
+    /// Receives the output of a task spawned by the state or a message sent by the state above
+    fn on_message(&mut self, message: Box<dyn Any>, state_data: &mut StateData<Data>) -> Transition<Data> { Transition::None }
+
+    /// Called before the state is removed, replaced or the application quits
+    fn can_exit(&mut self, state_data: &mut StateData<Data>) -> TransitionGuard { TransitionGuard::Allow }
+    /// Called before the state is added to the stack, it cannot spawn tasks yet
+    fn can_enter(&mut self, state_data: &mut StateData<Data>) -> TransitionGuard { TransitionGuard::Allow }
+
+    /// Name used for introspection, the type name by default
+    fn name(&self) -> &'static str { std::any::type_name::<Self>() }
 }
 
 
+/// A state and the id owning its tasks, given when it is added to the stack
+struct StackEntry<Data> {
+    id: usize,
+    state: StateRef<Data>
+}
+
 struct StatesStack<Data> {
-    states: VecDeque<StateRef<Data>>,
+    states: VecDeque<StackEntry<Data>>,
 }
 
 impl<Data> StatesStack<Data> {
@@ -59,105 +149,392 @@ impl<Data> StatesStack<Data> {
         }
     }
 
-    fn top(&mut self) -> &mut StateRef<Data> {
+    fn top(&mut self) -> &mut StackEntry<Data> {
         self.states.back_mut().unwrap()
     }
 
-    fn pop(&mut self) -> StateRef<Data> {
+    fn pop(&mut self) -> StackEntry<Data> {
         self.states.pop_back().unwrap()
     }
 
-    fn push(&mut self, state: StateRef<Data>) {
-        self.states.push_back(state);
+    fn push(&mut self, entry: StackEntry<Data>) {
+        self.states.push_back(entry);
     }
 
     fn has_state(&self) -> bool {
         self.states.len() > 0
     }
 
-    fn iter_mut(&mut self) -> IterMut<'_, StateRef<Data>> {
+    fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    fn get(&self, index: usize) -> &StackEntry<Data> {
+        &self.states[index]
+    }
+
+    fn iter_mut(&mut self) -> IterMut<'_, StackEntry<Data>> {
         self.states.iter_mut()
     }
+
+    fn top_name(&self) -> Option<&'static str> {
+        self.states.back().map(|entry| entry.state.name())
+    }
+
+    fn infos(&self) -> Vec<StateInfo> {
+        let len = self.states.len();
+        self.states.iter().enumerate().map(|(i, entry)| StateInfo {
+            name: entry.state.name(),
+            paused: i + 1 < len
+        }).collect()
+    }
 }
 
-pub(crate) struct StateMachine<Data> {
+/// Calls the function with the state as owner of the tasks spawned meanwhile
+fn with_owner<Data, R>(
+    entry: &mut StackEntry<Data>,
+    states_data: &mut StateData<Data>,
+    func: impl FnOnce(&mut StateRef<Data>, &mut StateData<Data>) -> R) -> R
+{
+    let previous_owner = states_data.tasks.set_current_owner(entry.id);
+    let res = func(&mut entry.state, states_data);
+    states_data.tasks.set_current_owner(previous_owner);
+    res
+}
+
+fn changes_stack<Data>(trans: &Transition<Data>) -> bool {
+    matches!(trans, Transition::Add(_) | Transition::Replace(_) | Transition::Remove)
+}
+
+/// A stack of states
+///
+/// The application owns the root machine, a state can own its own machine
+/// and forward it the events, the updates and the rendering to have sub-states.
+///
+/// The transitions returned by the states are queued and applied at the end of `on_update`,
+/// the transitions that must be handled by the owner of the machine are then returned
+pub struct StateMachine<Data> {
     states_stack: StatesStack<Data>,
-    pub(crate) states_data: StateData<Data>
+    pending_transitions: VecDeque<Transition<Data>>,
+    /// Only the root machine is reflected in `StateData::states_info`
+    is_root: bool
 }
 
 impl<Data> StateMachine<Data> {
-    pub(crate) fn new(initial_state: StateRef<Data>, states_data: StateData<Data>) -> Self {
-        let mut res = Self {
+    pub fn new() -> Self {
+        Self {
             states_stack: StatesStack::new(),
-            states_data
+            pending_transitions: VecDeque::new(),
+            is_root: false
+        }
+    }
+
+    pub(crate) fn root(initial_state: StateRef<Data>, states_data: &mut StateData<Data>) -> Self {
+        let mut res = Self::new();
+        res.is_root = true;
+
+        res.add_state(initial_state, states_data, false);
+        res.record(TransitionKind::Add, None, states_data);
+
+        res
+    }
+
+    pub fn with_initial_state(initial_state: StateRef<Data>, states_data: &mut StateData<Data>) -> Self {
+        let mut res = Self::new();
+
+        res.add_state(initial_state, states_data, false);
+
+        res
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.states_stack.has_state()
+    }
+
+    /// Delivers the completed tasks, updates the top state then applies the pending transitions
+    pub fn on_update(&mut self, states_data: &mut StateData<Data>) -> Transition<Data> {
+        self.deliver_tasks_outputs(states_data);
+
+        if !self.is_empty() {
+            let trans = with_owner(self.states_stack.top(), states_data, |state, states_data| state.on_update(states_data));
+            self.push_transition(trans);
+        }
+
+        self.apply_transitions(states_data)
+    }
+
+    /// Gives the event to the top state, then to the states below it until one consumes it.
+    /// The transitions changing the stack returned by the states below the top one are dropped,
+    /// they would be applied to the top state
+    pub fn on_event(&mut self, event: Event, states_data: &mut StateData<Data>) {
+        let mut transitions = Vec::new();
+
+        for (i, entry) in self.states_stack.iter_mut().rev().enumerate() {
+            if i > 0 && !entry.state.receives_propagated_events() {
+                continue;
+            }
+
+            let response = with_owner(entry, states_data, |state, states_data| state.handle_event(event, states_data));
+            if i == 0 || !changes_stack(&response.transition) {
+                transitions.push(response.transition);
+            }
+            if response.consumed {
+                break;
+            }
+        }
+
+        for trans in transitions {
+            self.push_transition(trans);
+        }
+    }
+
+    /// Queues the transition, only the first transition changing the stack is kept until it is applied
+    pub fn push_transition(&mut self, trans: Transition<Data>) {
+        let is_duplicate = match &trans {
+            Transition::None => true,
+            Transition::Quit => self.pending_transitions.iter().any(|t| matches!(t, Transition::Quit)),
+            Transition::Parent(_) => false,
+            _ => self.pending_transitions.iter().any(changes_stack)
         };
 
-        res.add_state(initial_state, false);
+        if !is_duplicate {
+            self.pending_transitions.push_back(trans);
+        }
+    }
+
+    pub fn has_pending_transitions(&self) -> bool {
+        !self.pending_transitions.is_empty()
+    }
+
+    /// Applies the queued transitions allowed by the guards of the states,
+    /// returns the transition that must be handled by the owner of the machine
+    pub fn apply_transitions(&mut self, states_data: &mut StateData<Data>) -> Transition<Data> {
+        let mut res = Transition::None;
+
+        while let Some(mut trans) = self.pending_transitions.pop_front() {
+            match self.check_guards(&mut trans, states_data) {
+                TransitionGuard::Allow => {
+                    match self.transition(trans, states_data) {
+                        Transition::None => {},
+                        trans if matches!(res, Transition::None) || matches!(trans, Transition::Quit) => res = trans,
+                        _ => {}
+                    }
+                },
+                TransitionGuard::Deny => {},
+                TransitionGuard::Defer => {
+                    self.pending_transitions.push_front(trans);
+                    break;
+                }
+            }
+        }
 
         res
+    }
+
+    fn deliver_tasks_outputs(&mut self, states_data: &mut StateData<Data>) {
+        let mut transitions = Vec::new();
 
+        for state in self.states_stack.iter_mut() {
+            for output in states_data.tasks.take_completed(state.id) {
+                transitions.push(match output {
+                    TaskOutput::Message(message) => {
+                        with_owner(state, states_data, |state, states_data| state.on_message(message, states_data))
+                    },
+                    TaskOutput::Transition(trans) => trans
+                });
+            }
+        }
+
+        for trans in transitions {
+            self.push_transition(trans);
+        }
     }
 
-    pub(crate) fn on_update(&mut self) -> AppSignal {
-        let trans = self.states_stack.top().on_update(&mut self.states_data);
+    fn check_guards(&mut self, trans: &mut Transition<Data>, states_data: &mut StateData<Data>) -> TransitionGuard {
+        let exit_guard = match trans {
+            Transition::Remove | Transition::Replace(_) | Transition::Quit if !self.is_empty() => {
+                with_owner(self.states_stack.top(), states_data, |state, states_data| state.can_exit(states_data))
+            },
+            _ => TransitionGuard::Allow
+        };
+
+        if exit_guard != TransitionGuard::Allow {
+            return exit_guard;
+        }
 
-        self.transition(trans)
+        match trans {
+            Transition::Add(new_state) | Transition::Replace(new_state) => {
+                let previous_owner = states_data.tasks.set_current_owner(NO_OWNER);
+                let guard = new_state.can_enter(states_data);
+                states_data.tasks.set_current_owner(previous_owner);
+                guard
+            },
+            _ => TransitionGuard::Allow
+        }
     }
 
-    pub(crate) fn on_event(&mut self, event: Event) -> AppSignal {
-        let trans = self.states_stack.top().on_event(event, &mut self.states_data);
+    pub fn on_render(&mut self, states_data: &mut StateData<Data>, target: &mut dyn RenderTarget) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+
+        // Overlays are rendered over the states below them
+        let mut first = self.states_stack.len() - 1;
+        while first > 0 && self.states_stack.get(first).state.is_overlay() {
+            first -= 1;
+        }
 
-        self.transition(trans)
+        let mut rendered = false;
+        for state in self.states_stack.iter_mut().skip(first) {
+            rendered |= with_owner(state, states_data, |state, states_data| state.on_render(states_data, target));
+        }
+        rendered
     }
 
-    pub(crate) fn on_render(&mut self, target: &mut dyn RenderTarget) -> bool {
-        self.states_stack.top().on_render(&mut self.states_data, target)
+    pub fn on_pause(&mut self, states_data: &mut StateData<Data>) {
+        if !self.is_empty() {
+            with_owner(self.states_stack.top(), states_data, |state, states_data| state.on_pause(states_data));
+        }
+    }
+
+    pub fn on_resume(&mut self, states_data: &mut StateData<Data>) {
+        if !self.is_empty() {
+            with_owner(self.states_stack.top(), states_data, |state, states_data| state.on_resume(states_data));
+        }
     }
 
-    pub(crate) fn terminate(&mut self) {
+    /// Ends all the states of the machine
+    pub fn terminate(&mut self, states_data: &mut StateData<Data>) {
         for state in self.states_stack.iter_mut() {
-            state.on_end(&mut self.states_data);
+            with_owner(state, states_data, |state, states_data| state.on_end(states_data));
+            states_data.tasks.cancel(state.id);
         }
+        self.states_stack.states.clear();
+        self.update_states_info(states_data);
     }
 
-    fn transition(&mut self, trans: Transition<Data>) -> AppSignal {
+    fn transition(&mut self, trans: Transition<Data>, states_data: &mut StateData<Data>) -> Transition<Data> {
+        let from = self.states_stack.top_name();
         match trans {
             Transition::Add(new_state) => {
-                self.add_state(new_state, true);
-                AppSignal::None
+                self.add_state(new_state, states_data, true);
+                self.record(TransitionKind::Add, from, states_data);
+                Transition::None
             },
             Transition::Remove => {
-                self.remove_state(true);
-                AppSignal::None
+                self.remove_state(states_data, true);
+                self.record(TransitionKind::Remove, from, states_data);
+                Transition::None
             },
             Transition::Replace(new_state) => {
-                self.remove_state(false);
-                self.add_state(new_state, false);
-                AppSignal::None
+                self.remove_state(states_data, false);
+                self.add_state(new_state, states_data, false);
+                self.record(TransitionKind::Replace, from, states_data);
+                Transition::None
             },
-            Transition::None => AppSignal::None,
-            Transition::Quit => AppSignal::Quit,
+            Transition::None => Transition::None,
+            Transition::Quit => {
+                self.record(TransitionKind::Quit, from, states_data);
+                Transition::Quit
+            },
+            Transition::Parent(trans) => *trans
+        }
+    }
+
+    fn record(&self, kind: TransitionKind, from: Option<&'static str>, states_data: &mut StateData<Data>) {
+        if self.is_root {
+            states_data.states_info.record(kind, from, self.states_stack.top_name());
+        }
+    }
+
+    fn update_states_info(&self, states_data: &mut StateData<Data>) {
+        if self.is_root {
+            states_data.states_info.set_stack(self.states_stack.infos());
         }
     }
 
-    fn add_state(&mut self, new_state: StateRef<Data>, pause: bool) {
+    fn add_state(&mut self, new_state: StateRef<Data>, states_data: &mut StateData<Data>, pause: bool) {
         if self.states_stack.has_state() && pause {
-            self.states_stack.top().on_pause(&mut self.states_data);
+            with_owner(self.states_stack.top(), states_data, |state, states_data| state.on_pause(states_data));
         }
 
-        self.states_stack.push(new_state);
+        let id = states_data.tasks.new_state_id();
+        self.states_stack.push(StackEntry {
+            id,
+            state: new_state
+        });
+        self.update_states_info(states_data);
+
+        let len = self.states_stack.len();
+        if len > 1 {
+            states_data.tasks.set_below(id, self.states_stack.get(len - 2).id);
+        }
 
         let top_state = self.states_stack.top();
-        top_state.on_init(&mut self.states_data);
+        with_owner(top_state, states_data, |state, states_data| state.on_init(states_data));
     }
 
-    fn remove_state(&mut self, resume: bool) {
+    fn remove_state(&mut self, states_data: &mut StateData<Data>, resume: bool) {
         let mut removed_state = self.states_stack.pop();
+        self.update_states_info(states_data);
 
-        removed_state.on_end(&mut self.states_data);
+        with_owner(&mut removed_state, states_data, |state, states_data| state.on_end(states_data));
+        states_data.tasks.cancel(removed_state.id);
 
         if self.states_stack.has_state() && resume {
-            self.states_stack.top().on_resume(&mut self.states_data);
+            with_owner(self.states_stack.top(), states_data, |state, states_data| state.on_resume(states_data));
+        }
+    }
+}
+
+impl<Data> Default for StateMachine<Data> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct RemovedOnEvent;
+
+    impl State<()> for RemovedOnEvent {
+        fn on_event(&mut self, event: Event, state_data: &mut StateData<()>) -> Transition<()> {
+            Transition::Remove
+        }
+    }
+
+    struct Overlay;
+
+    impl State<()> for Overlay {
+        fn handle_event(&mut self, event: Event, state_data: &mut StateData<()>) -> EventResponse<()> {
+            EventResponse::ignored(Transition::None)
         }
+
+        fn is_overlay(&self) -> bool { true }
+    }
+
+    #[test]
+    fn propagated_remove_does_not_pop_the_overlay() {
+        let mut states_data = StateData::new(());
+        let mut machine = StateMachine::with_initial_state(Box::new(RemovedOnEvent), &mut states_data);
+        machine.push_transition(Transition::Add(Box::new(Overlay)));
+        machine.on_update(&mut states_data);
+        assert_eq!(machine.states_stack.len(), 2);
+
+        machine.on_event(Event::GainedFocus, &mut states_data);
+        machine.on_update(&mut states_data);
+        assert_eq!(machine.states_stack.len(), 2);
+        assert_eq!(machine.states_stack.top_name(), Some(Overlay.name()));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn top_state_remove_is_applied() {
+        let mut states_data = StateData::new(());
+        let mut machine = StateMachine::with_initial_state(Box::new(RemovedOnEvent), &mut states_data);
+        machine.on_event(Event::GainedFocus, &mut states_data);
+        machine.on_update(&mut states_data);
+        assert!(machine.is_empty());
+    }
+}
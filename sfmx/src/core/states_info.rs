@@ -0,0 +1,104 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+use super::timer::Timer;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateInfo {
+    pub name: &'static str,
+    pub paused: bool
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransitionKind {
+    Add,
+    Replace,
+    Remove,
+    Quit
+}
+
+#[derive(Clone, Debug)]
+pub struct TransitionRecord {
+    pub kind: TransitionKind,
+    /// State on top of the stack before the transition
+    pub from: Option<&'static str>,
+    /// State on top of the stack after the transition
+    pub to: Option<&'static str>,
+    /// Time elapsed since the start of the application
+    pub time: Duration
+}
+
+/// Read only view of the application states stack, from bottom to top,
+/// with the last transitions applied to it
+pub struct StatesInfo {
+    stack: Vec<StateInfo>,
+    history: VecDeque<TransitionRecord>,
+    history_capacity: usize,
+    timer: Timer
+}
+
+impl StatesInfo {
+    pub(crate) fn new() -> Self {
+        Self {
+            stack: Vec::new(),
+            history: VecDeque::new(),
+            history_capacity: 32,
+            timer: Timer::new()
+        }
+    }
+
+    pub fn stack(&self) -> &[StateInfo] {
+        &self.stack
+    }
+
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn top(&self) -> Option<&StateInfo> {
+        self.stack.last()
+    }
+
+    /// State right under the top one
+    pub fn below_top(&self) -> Option<&StateInfo> {
+        self.stack.iter().rev().nth(1)
+    }
+
+    pub fn is_on_top<S>(&self) -> bool {
+        self.top().map(|state| state.name == std::any::type_name::<S>()).unwrap_or(false)
+    }
+
+    pub fn contains<S>(&self) -> bool {
+        self.stack.iter().any(|state| state.name == std::any::type_name::<S>())
+    }
+
+    pub fn history(&self) -> &VecDeque<TransitionRecord> {
+        &self.history
+    }
+
+    pub fn set_history_capacity(&mut self, capacity: usize) {
+        self.history_capacity = capacity;
+        while self.history.len() > capacity {
+            self.history.pop_front();
+        }
+    }
+
+    pub(crate) fn set_stack(&mut self, stack: Vec<StateInfo>) {
+        self.stack = stack;
+    }
+
+    pub(crate) fn record(&mut self, kind: TransitionKind, from: Option<&'static str>, to: Option<&'static str>) {
+        if self.history_capacity == 0 {
+            return;
+        }
+        if self.history.len() == self.history_capacity {
+            self.history.pop_front();
+        }
+
+        self.history.push_back(TransitionRecord {
+            kind,
+            from,
+            to,
+            time: self.timer.elapsed()
+        });
+    }
+}
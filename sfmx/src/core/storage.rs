@@ -0,0 +1,350 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::rc::Rc;
+use serde::{Serialize, de::DeserializeOwned};
+use toml::{Table, Value};
+
+/// Where the storage documents are kept, a document is a toml string identified by a name
+pub trait StorageBackend {
+    fn read(&self, name: &str) -> Result<Option<String>, String>;
+    /// The previous content must be replaced atomically
+    fn write(&mut self, name: &str, content: &str) -> Result<(), String>;
+    fn remove(&mut self, name: &str) -> Result<(), String>;
+}
+
+//////////////// File backend ////////////////////////
+
+/// Documents stored as `<name>.toml` files in a directory
+pub struct FileBackend {
+    directory: PathBuf
+}
+
+impl FileBackend {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into()
+        }
+    }
+
+    /// Directory of the application in the per-user config directory
+    pub fn in_config_dir(app_name: &str) -> Result<Self, String> {
+        let config_dir = config_directory().ok_or("Cannot find the user config directory".to_string())?;
+        Ok(Self::new(config_dir.join(app_name)))
+    }
+
+    pub fn directory(&self) -> &PathBuf {
+        &self.directory
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.directory.join(format!("{}.toml", name))
+    }
+}
+
+fn config_directory() -> Option<PathBuf> {
+    let home = || std::env::var_os("HOME").map(PathBuf::from);
+    if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    }
+    else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library/Application Support"))
+    }
+    else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .or_else(|| home().map(|home| home.join(".config")))
+    }
+}
+
+impl StorageBackend for FileBackend {
+    fn read(&self, name: &str) -> Result<Option<String>, String> {
+        match std::fs::read_to_string(self.path(name)) {
+            Ok(content) => Ok(Some(content)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(format!("Cannot read {}: {}", name, error))
+        }
+    }
+
+    /// Writes a temporary file then renames it, so a crash never leaves a truncated document
+    fn write(&mut self, name: &str, content: &str) -> Result<(), String> {
+        let error = |error: std::io::Error| format!("Cannot write {}: {}", name, error);
+        std::fs::create_dir_all(&self.directory).map_err(error)?;
+
+        let path = self.path(name);
+        let temp_path = path.with_extension("toml.tmp");
+        let mut file = std::fs::File::create(&temp_path).map_err(error)?;
+        file.write_all(content.as_bytes()).map_err(error)?;
+        file.sync_all().map_err(error)?;
+        std::fs::rename(&temp_path, &path).map_err(error)
+    }
+
+    fn remove(&mut self, name: &str) -> Result<(), String> {
+        match std::fs::remove_file(self.path(name)) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(format!("Cannot remove {}: {}", name, error)),
+            _ => Ok(())
+        }
+    }
+}
+
+//////////////// Memory backend ////////////////////////
+
+/// Documents kept in memory, the clones share the same documents
+#[derive(Clone, Default)]
+pub struct MemoryBackend {
+    documents: Rc<RefCell<HashMap<String, String>>>
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self {
+            documents: Rc::new(RefCell::new(HashMap::new()))
+        }
+    }
+
+    pub fn document(&self, name: &str) -> Option<String> {
+        self.documents.borrow().get(name).cloned()
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    fn read(&self, name: &str) -> Result<Option<String>, String> {
+        Ok(self.document(name))
+    }
+
+    fn write(&mut self, name: &str, content: &str) -> Result<(), String> {
+        self.documents.borrow_mut().insert(name.to_string(), content.to_string());
+        Ok(())
+    }
+
+    fn remove(&mut self, name: &str) -> Result<(), String> {
+        self.documents.borrow_mut().remove(name);
+        Ok(())
+    }
+}
+
+//////////////////////////////////////
+
+const PREFERENCES_DOCUMENT: &str = "preferences";
+
+/// Upgrades the data of a document from a version to the next one
+type Migration = Box<dyn Fn(&str, &mut Value) -> Result<(), String>>;
+
+/// Preferences and save files of the application
+///
+/// Each document stores its data with the storage version it was written with,
+/// older documents go through the migrations when they are read.
+/// The preferences are loaded on first access and written by `flush`,
+/// which the application calls when it exits.
+/// If the stored preferences cannot be read, the defaults are used
+/// and `flush` refuses to write over the stored file.
+pub struct Storage {
+    backend: Box<dyn StorageBackend>,
+    version: u32,
+    migrations: HashMap<u32, Migration>,
+    preferences: Option<Table>,
+    load_error: Option<String>,
+    dirty: bool
+}
+
+impl Storage {
+    pub fn new(backend: impl StorageBackend + 'static) -> Self {
+        Self {
+            backend: Box::new(backend),
+            version: 0,
+            migrations: HashMap::new(),
+            preferences: None,
+            load_error: None,
+            dirty: false
+        }
+    }
+
+    pub fn in_memory() -> Self {
+        Self::new(MemoryBackend::new())
+    }
+
+    /// Storage in the per-user config directory, fails if it cannot be found
+    pub fn in_config_dir(app_name: &str) -> Result<Self, String> {
+        FileBackend::in_config_dir(app_name).map(Self::new)
+    }
+
+    /// Version written in the documents
+    pub fn with_version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Migration applied to the documents of version `from_version`, the document name is given with its data
+    pub fn with_migration(mut self, from_version: u32, migration: impl Fn(&str, &mut Value) -> Result<(), String> + 'static) -> Self {
+        self.migrations.insert(from_version, Box::new(migration));
+        self
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    //////////////// Preferences ////////////////////////
+
+    pub fn get<T: DeserializeOwned>(&mut self, key: &str) -> Option<T> {
+        self.preferences().get(key)?.clone().try_into().ok()
+    }
+
+    pub fn get_or<T: DeserializeOwned>(&mut self, key: &str, default: T) -> T {
+        self.get(key).unwrap_or(default)
+    }
+
+    pub fn set<T: Serialize>(&mut self, key: &str, value: &T) -> Result<(), String> {
+        let value = Value::try_from(value).map_err(|e| format!("Cannot store {}: {}", key, e))?;
+        self.preferences().insert(key.to_string(), value);
+        self.dirty = true;
+        Ok(())
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        if self.preferences().remove(key).is_some() {
+            self.dirty = true;
+        }
+    }
+
+    pub fn contains(&mut self, key: &str) -> bool {
+        self.preferences().contains_key(key)
+    }
+
+    /// Error of the loading of the stored preferences, the defaults are used in its place
+    pub fn load_error(&mut self) -> Option<&str> {
+        self.preferences();
+        self.load_error.as_deref()
+    }
+
+    /// Writes the preferences if they changed
+    ///
+    /// Fails without writing if the stored preferences could not be loaded,
+    /// so that they are not replaced by the defaults.
+    pub fn flush(&mut self) -> Result<(), String> {
+        if !self.dirty {
+            return Ok(());
+        }
+        if let Some(error) = &self.load_error {
+            return Err(format!("{}, the preferences are not written over the stored ones", error));
+        }
+
+        let preferences = Value::Table(self.preferences().clone());
+        self.write_document(PREFERENCES_DOCUMENT, preferences)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    fn preferences(&mut self) -> &mut Table {
+        if self.preferences.is_none() {
+            let preferences = match self.read_document(PREFERENCES_DOCUMENT) {
+                Ok(Some(Value::Table(table))) => table,
+                Ok(_) => Table::new(),
+                Err(error) => {
+                    self.load_error = Some(error);
+                    Table::new()
+                }
+            };
+            self.preferences = Some(preferences);
+        }
+        self.preferences.as_mut().unwrap()
+    }
+
+    //////////////// Save files ////////////////////////
+
+    /// Writes the value in its own document
+    pub fn save<T: Serialize>(&mut self, name: &str, value: &T) -> Result<(), String> {
+        if name == PREFERENCES_DOCUMENT {
+            return Err(format!("{} is reserved for the preferences", PREFERENCES_DOCUMENT));
+        }
+        let data = Value::try_from(value).map_err(|e| format!("Cannot save {}: {}", name, e))?;
+        self.write_document(name, data)
+    }
+
+    /// None if the document does not exist
+    pub fn load<T: DeserializeOwned>(&mut self, name: &str) -> Result<Option<T>, String> {
+        match self.read_document(name)? {
+            Some(data) => data.try_into().map(Some).map_err(|e| format!("Invalid document {}: {}", name, e)),
+            None => Ok(None)
+        }
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<(), String> {
+        self.backend.remove(name)
+    }
+
+    //////////////////////////////////////
+
+    fn read_document(&self, name: &str) -> Result<Option<Value>, String> {
+        let content = match self.backend.read(name)? {
+            Some(content) => content,
+            None => return Ok(None)
+        };
+
+        let mut document = content.parse::<Table>().map_err(|e| format!("Invalid document {}: {}", name, e))?;
+        let version = document.get("version").and_then(Value::as_integer).unwrap_or(0) as u32;
+        let mut data = document.remove("data").unwrap_or(Value::Table(Table::new()));
+
+        if version > self.version {
+            return Err(format!("Document {} has version {} which is newer than {}", name, version, self.version));
+        }
+
+        for from_version in version..self.version {
+            if let Some(migration) = self.migrations.get(&from_version) {
+                migration(name, &mut data)?;
+            }
+        }
+        Ok(Some(data))
+    }
+
+    fn write_document(&mut self, name: &str, data: Value) -> Result<(), String> {
+        let mut document = Table::new();
+        document.insert("version".to_string(), Value::Integer(self.version as i64));
+        document.insert("data".to_string(), data);
+
+        let content = toml::to_string(&document).map_err(|e| format!("Cannot serialize {}: {}", name, e))?;
+        self.backend.write(name, &content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unreadable_preferences_are_not_overwritten() {
+        let mut backend = MemoryBackend::new();
+        let stored = "version = 2\n[data]\nvolume = 0.5\n";
+        backend.write(PREFERENCES_DOCUMENT, stored).unwrap();
+
+        let mut storage = Storage::new(backend.clone()).with_version(1);
+        assert_eq!(storage.get::<f64>("volume"), None);
+        assert!(storage.load_error().is_some());
+
+        storage.set("volume", &1.0).unwrap();
+        assert!(storage.flush().is_err());
+        assert_eq!(backend.document(PREFERENCES_DOCUMENT).as_deref(), Some(stored));
+    }
+
+    #[test]
+    fn preferences_document_cannot_be_saved() {
+        let backend = MemoryBackend::new();
+        let mut storage = Storage::new(backend.clone());
+        assert!(storage.save(PREFERENCES_DOCUMENT, &1).is_err());
+        assert_eq!(backend.document(PREFERENCES_DOCUMENT), None);
+    }
+
+    #[test]
+    fn preferences_are_written_on_flush() {
+        let backend = MemoryBackend::new();
+        let mut storage = Storage::new(backend.clone()).with_version(1);
+        storage.set("volume", &0.5).unwrap();
+        storage.flush().unwrap();
+
+        let mut storage = Storage::new(backend).with_version(1);
+        assert_eq!(storage.load_error(), None);
+        assert_eq!(storage.get::<f64>("volume"), Some(0.5));
+    }
+}
@@ -0,0 +1,234 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::{Duration, Instant};
+use super::state_machine::Transition;
+
+/// Owner of the tasks spawned outside of the states calls
+pub(crate) const NO_OWNER: usize = 0;
+
+pub enum TaskOutput<Data> {
+    /// Given to `State::on_message` of the state that spawned the task
+    Message(Box<dyn Any>),
+    /// Queued in the state machine of the state that spawned the task
+    Transition(Transition<Data>)
+}
+
+struct Task<Data> {
+    owner: usize,
+    future: Pin<Box<dyn Future<Output = TaskOutput<Data>>>>
+}
+
+struct NoopWaker;
+
+impl Wake for NoopWaker {
+    fn wake(self: Arc<Self>) {}
+}
+
+/// Single threaded executor polling the tasks of the states once per frame
+///
+/// A task belongs to the state that spawned it, its output is delivered to this state
+/// before its next update and the task is dropped if the state ends before completion.
+///
+/// Tasks are spawned from the calls of a state added to a state machine, `on_init`,
+/// `on_update`, `handle_event`, `on_message`, `on_render`, `on_pause`, `on_resume`
+/// and `can_exit`. The tasks spawned by `on_end` are dropped with the state.
+/// `can_enter` is called before the state is added, spawning there or outside
+/// of the states calls fails as no state could receive the output.
+pub struct Tasks<Data> {
+    tasks: Vec<Task<Data>>,
+    completed: Vec<(usize, TaskOutput<Data>)>,
+    current_owner: usize,
+    /// State right below each state when it was added
+    below: HashMap<usize, usize>,
+    last_state_id: usize,
+    waker: Waker
+}
+
+impl<Data> Tasks<Data> {
+    pub(crate) fn new() -> Self {
+        Self {
+            tasks: Vec::new(),
+            completed: Vec::new(),
+            current_owner: NO_OWNER,
+            below: HashMap::new(),
+            last_state_id: NO_OWNER,
+            waker: Waker::from(Arc::new(NoopWaker))
+        }
+    }
+
+    /// Spawns a task, its output is given to `State::on_message` when it completes
+    pub fn spawn<T: 'static>(&mut self, future: impl Future<Output = T> + 'static) -> Result<(), String> {
+        self.spawn_task(async move {
+            TaskOutput::Message(Box::new(future.await))
+        })
+    }
+
+    /// Spawns a task, the transition it returns is applied when it completes
+    pub fn spawn_transition(&mut self, future: impl Future<Output = Transition<Data>> + 'static) -> Result<(), String> {
+        self.spawn_task(async move {
+            TaskOutput::Transition(future.await)
+        })
+    }
+
+    fn spawn_task(&mut self, future: impl Future<Output = TaskOutput<Data>> + 'static) -> Result<(), String> {
+        if self.current_owner == NO_OWNER {
+            return Err("Tasks can only be spawned by a state added to a state machine".to_string());
+        }
+        self.tasks.push(Task {
+            owner: self.current_owner,
+            future: Box::pin(future)
+        });
+        Ok(())
+    }
+
+    /// Sends a message to `State::on_message` of the state below the current one,
+    /// it is delivered before the next update even if the current state is removed meanwhile
+    pub fn send_below<T: 'static>(&mut self, message: T) {
+        if let Some(below) = self.below.get(&self.current_owner) {
+            self.completed.push((*below, TaskOutput::Message(Box::new(message))));
+        }
+    }
+
+    pub fn running_tasks(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub(crate) fn poll(&mut self) {
+        let mut context = Context::from_waker(&self.waker);
+        let mut i = 0;
+        while i < self.tasks.len() {
+            match self.tasks[i].future.as_mut().poll(&mut context) {
+                Poll::Ready(output) => {
+                    let task = self.tasks.swap_remove(i);
+                    self.completed.push((task.owner, output));
+                },
+                Poll::Pending => i += 1
+            }
+        }
+    }
+
+    /// Id of a state added to a state machine, the ids are never reused
+    pub(crate) fn new_state_id(&mut self) -> usize {
+        self.last_state_id += 1;
+        self.last_state_id
+    }
+
+    pub(crate) fn set_current_owner(&mut self, owner: usize) -> usize {
+        std::mem::replace(&mut self.current_owner, owner)
+    }
+
+    pub(crate) fn take_completed(&mut self, owner: usize) -> Vec<TaskOutput<Data>> {
+        let mut res = Vec::new();
+        let mut i = 0;
+        while i < self.completed.len() {
+            if self.completed[i].0 == owner {
+                res.push(self.completed.remove(i).1);
+            }
+            else {
+                i += 1;
+            }
+        }
+        res
+    }
+
+    pub(crate) fn set_below(&mut self, owner: usize, below: usize) {
+        self.below.insert(owner, below);
+    }
+
+    pub(crate) fn cancel(&mut self, owner: usize) {
+        self.below.remove(&owner);
+        self.tasks.retain(|task| task.owner != owner);
+        self.completed.retain(|(task_owner, _)| *task_owner != owner);
+    }
+}
+
+//////////////// Futures ////////////////////////
+
+/// Completes on the next frame
+pub fn next_frame() -> impl Future<Output = ()> {
+    let mut yielded = false;
+    std::future::poll_fn(move |_| {
+        if yielded {
+            Poll::Ready(())
+        }
+        else {
+            yielded = true;
+            Poll::Pending
+        }
+    })
+}
+
+/// Completes on the first frame after the duration elapsed
+pub fn wait(duration: Duration) -> impl Future<Output = ()> {
+    let end = Instant::now() + duration;
+    std::future::poll_fn(move |_| {
+        if Instant::now() >= end {
+            Poll::Ready(())
+        }
+        else {
+            Poll::Pending
+        }
+    })
+}
+
+/// Runs the function in a new thread, completes with its result or the panic message of the thread
+pub fn run_in_thread<T: Send + 'static>(func: impl FnOnce() -> T + Send + 'static) -> impl Future<Output = Result<T, String>> {
+    let mut handle = Some(std::thread::spawn(func));
+
+    std::future::poll_fn(move |_| {
+        match handle.take_if(|handle| handle.is_finished()) {
+            Some(handle) => Poll::Ready(handle.join().map_err(|payload| {
+                let message = payload.downcast_ref::<&str>().copied()
+                    .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                    .unwrap_or("unknown error");
+                format!("The task thread panicked: {}", message)
+            })),
+            None => Poll::Pending
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Polls the future until it completes
+    fn block_on<T>(future: impl Future<Output = T>) -> T {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut context = Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+            std::thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn spawning_without_owner_fails() {
+        let mut tasks = Tasks::<()>::new();
+        assert!(tasks.spawn(async {}).is_err());
+        assert!(tasks.spawn_transition(async { Transition::None }).is_err());
+        assert_eq!(tasks.running_tasks(), 0);
+
+        let owner = tasks.new_state_id();
+        tasks.set_current_owner(owner);
+        assert!(tasks.spawn(async { 1 }).is_ok());
+        tasks.poll();
+        assert_eq!(tasks.running_tasks(), 0);
+        assert_eq!(tasks.take_completed(owner).len(), 1);
+    }
+
+    #[test]
+    fn thread_result_or_panic() {
+        assert_eq!(block_on(run_in_thread(|| 6 * 7)), Ok(42));
+
+        let error = block_on(run_in_thread(|| -> u32 { panic!("no data") })).unwrap_err();
+        assert!(error.contains("no data"), "{}", error);
+    }
+}
@@ -0,0 +1,817 @@
+use std::f32::consts::PI;
+use crate::sfml_export::*;
+use crate::gui::{Widget, DynamicText};
+
+//////////////// Easing ////////////////////////
+
+/// Easing curves, mapping the progress of a tween in [0, 1] to its interpolation factor
+#[derive(Clone, Copy, Debug)]
+pub enum Easing {
+    Linear,
+    QuadIn, QuadOut, QuadInOut,
+    CubicIn, CubicOut, CubicInOut,
+    QuartIn, QuartOut, QuartInOut,
+    QuintIn, QuintOut, QuintInOut,
+    SineIn, SineOut, SineInOut,
+    ExpoIn, ExpoOut, ExpoInOut,
+    CircIn, CircOut, CircInOut,
+    /// Goes slightly backward before moving
+    BackIn, BackOut, BackInOut,
+    ElasticIn, ElasticOut, ElasticInOut,
+    BounceIn, BounceOut, BounceInOut,
+    Custom(fn(f32) -> f32)
+}
+
+const BACK_C1: f32 = 1.70158;
+const BACK_C2: f32 = BACK_C1 * 1.525;
+
+fn bounce_out(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+    if t < 1.0 / D {
+        N * t * t
+    }
+    else if t < 2.0 / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    }
+    else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    }
+    else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984375
+    }
+}
+
+/// Builds the in-out curve from the in curve
+fn in_out(t: f32, ease_in: impl Fn(f32) -> f32) -> f32 {
+    if t < 0.5 {
+        ease_in(t * 2.0) / 2.0
+    }
+    else {
+        1.0 - ease_in((1.0 - t) * 2.0) / 2.0
+    }
+}
+
+impl Easing {
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        let out = |ease_in: fn(f32) -> f32| 1.0 - ease_in(1.0 - t);
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => out(|t| t * t),
+            Easing::QuadInOut => in_out(t, |t| t * t),
+            Easing::CubicIn => t.powi(3),
+            Easing::CubicOut => out(|t| t.powi(3)),
+            Easing::CubicInOut => in_out(t, |t| t.powi(3)),
+            Easing::QuartIn => t.powi(4),
+            Easing::QuartOut => out(|t| t.powi(4)),
+            Easing::QuartInOut => in_out(t, |t| t.powi(4)),
+            Easing::QuintIn => t.powi(5),
+            Easing::QuintOut => out(|t| t.powi(5)),
+            Easing::QuintInOut => in_out(t, |t| t.powi(5)),
+            Easing::SineIn => 1.0 - (t * PI / 2.0).cos(),
+            Easing::SineOut => (t * PI / 2.0).sin(),
+            Easing::SineInOut => (1.0 - (t * PI).cos()) / 2.0,
+            Easing::ExpoIn => expo_in(t),
+            Easing::ExpoOut => out(expo_in),
+            Easing::ExpoInOut => in_out(t, expo_in),
+            Easing::CircIn => circ_in(t),
+            Easing::CircOut => out(circ_in),
+            Easing::CircInOut => in_out(t, circ_in),
+            Easing::BackIn => back_in(t, BACK_C1),
+            Easing::BackOut => out(|t| back_in(t, BACK_C1)),
+            Easing::BackInOut => in_out(t, |t| back_in(t, BACK_C2)),
+            Easing::ElasticIn => elastic_in(t),
+            Easing::ElasticOut => out(elastic_in),
+            Easing::ElasticInOut => in_out(t, elastic_in),
+            Easing::BounceIn => 1.0 - bounce_out(1.0 - t),
+            Easing::BounceOut => bounce_out(t),
+            Easing::BounceInOut => in_out(t, |t| 1.0 - bounce_out(1.0 - t)),
+            Easing::Custom(ease) => ease(t)
+        }
+    }
+}
+
+fn expo_in(t: f32) -> f32 {
+    if t <= 0.0 { 0.0 } else { 2f32.powf(10.0 * t - 10.0) }
+}
+
+fn circ_in(t: f32) -> f32 {
+    1.0 - (1.0 - t * t).max(0.0).sqrt()
+}
+
+fn back_in(t: f32, c1: f32) -> f32 {
+    (c1 + 1.0) * t * t * t - c1 * t * t
+}
+
+fn elastic_in(t: f32) -> f32 {
+    if t <= 0.0 || t >= 1.0 {
+        return t;
+    }
+    -(2f32.powf(10.0 * t - 10.0)) * ((t * 10.0 - 10.75) * 2.0 * PI / 3.0).sin()
+}
+
+//////////////// Values ////////////////////////
+
+/// Values a tween can animate
+pub trait Tweenable: Copy {
+    /// `t` can go out of [0, 1] with the back and elastic easings
+    fn interpolate(from: Self, to: Self, t: f32) -> Self;
+}
+
+impl Tweenable for f32 {
+    fn interpolate(from: Self, to: Self, t: f32) -> Self {
+        from + (to - from) * t
+    }
+}
+
+impl Tweenable for Vector2f {
+    fn interpolate(from: Self, to: Self, t: f32) -> Self {
+        from + (to - from) * t
+    }
+}
+
+impl Tweenable for Color {
+    fn interpolate(from: Self, to: Self, t: f32) -> Self {
+        let channel = |from: u8, to: u8| f32::interpolate(from as f32, to as f32, t).round().clamp(0.0, 255.0) as u8;
+        Color::rgba(
+            channel(from.r, to.r),
+            channel(from.g, to.g),
+            channel(from.b, to.b),
+            channel(from.a, to.a)
+        )
+    }
+}
+
+/// Rotation in degrees, interpolated along the shortest turn
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Angle(pub f32);
+
+impl Tweenable for Angle {
+    fn interpolate(from: Self, to: Self, t: f32) -> Self {
+        let delta = (to.0 - from.0).rem_euclid(360.0);
+        let delta = if delta > 180.0 { delta - 360.0 } else { delta };
+        Angle(from.0 + delta * t)
+    }
+}
+
+/// Objects with a color, the tweens can animate it
+pub trait TweenColor {
+    fn tween_color(&self) -> Color;
+    fn set_tween_color(&mut self, color: Color);
+}
+
+macro_rules! impl_tween_color {
+    ($($t: ty => $get: ident, $set: ident);*) => {
+        $(impl TweenColor for $t {
+            fn tween_color(&self) -> Color {
+                self.$get()
+            }
+
+            fn set_tween_color(&mut self, color: Color) {
+                self.$set(color);
+            }
+        })*
+    };
+}
+
+impl_tween_color!(
+    Sprite<'_> => color, set_color;
+    RcSprite => color, set_color;
+    Text<'_> => fill_color, set_fill_color;
+    RectangleShape<'_> => fill_color, set_fill_color;
+    CircleShape<'_> => fill_color, set_fill_color;
+    ConvexShape<'_> => fill_color, set_fill_color;
+    DynamicText => color, set_color
+);
+
+//////////////// Animations ////////////////////////
+
+/// Something advancing with time and applied on a target
+///
+/// Implemented by `Tween` and by the groups of animations, `Sequence` and `Parallel`.
+/// The target is given on each update, so the animation does not borrow it.
+pub trait Animation<T: ?Sized> {
+    /// Advances by the time, usually `StateData::delta_time`, and applies the animation on the target.
+    /// Returns the time left when the animation finished during this update
+    fn update(&mut self, delta_time: f32, target: &mut T) -> f32;
+
+    fn is_finished(&self) -> bool;
+
+    /// Starts again from the beginning, used by the repeated groups
+    fn restart(&mut self);
+}
+
+type Callback<T> = Box<dyn FnMut(&mut T)>;
+
+/// Count of the repetitions of an animation, None repeats forever
+#[derive(Clone, Copy, Debug)]
+struct Repetitions {
+    count: Option<u32>,
+    done: u32
+}
+
+impl Repetitions {
+    fn new() -> Self {
+        Self {
+            count: Some(0),
+            done: 0
+        }
+    }
+
+    /// True when the animation plays once more
+    fn next(&mut self) -> bool {
+        if self.count.is_some_and(|count| self.done >= count) {
+            return false;
+        }
+        self.done += 1;
+        true
+    }
+}
+
+//////////////// Tween ////////////////////////
+
+/// Animates a value of the target from its current value, or an explicit one, to another
+///
+/// ```ignore
+/// let mut tween = Tween::position((100.0, 50.0), 0.5).with_easing(Easing::CubicOut);
+/// tween.update(state_data.delta_time, &mut sprite);
+/// ```
+pub struct Tween<T: ?Sized, V: Tweenable> {
+    get: fn(&T) -> V,
+    set: fn(&mut T, V),
+    from: Option<V>,
+    to: V,
+    duration: f32,
+    easing: Easing,
+    delay: f32,
+    yoyo: bool,
+    repetitions: Repetitions,
+    on_complete: Option<Callback<T>>,
+
+    delay_left: f32,
+    time: f32,
+    finished: bool
+}
+
+impl<T: ?Sized, V: Tweenable> Tween<T, V> {
+    /// Tween of the value read by `get` and written by `set`, lasting `duration` seconds
+    pub fn new(get: fn(&T) -> V, set: fn(&mut T, V), to: V, duration: f32) -> Self {
+        Self {
+            get,
+            set,
+            from: None,
+            to,
+            duration: duration.max(0.0),
+            easing: Easing::Linear,
+            delay: 0.0,
+            yoyo: false,
+            repetitions: Repetitions::new(),
+            on_complete: None,
+            delay_left: 0.0,
+            time: 0.0,
+            finished: false
+        }
+    }
+
+    /// Starts from this value instead of the value of the target when the tween starts
+    pub fn with_from(mut self, from: V) -> Self {
+        self.from = Some(from);
+        self
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Seconds to wait before starting
+    pub fn with_delay(mut self, delay: f32) -> Self {
+        self.delay = delay.max(0.0);
+        self.delay_left = self.delay;
+        self
+    }
+
+    /// Goes back to the initial value after reaching the final one, doubling the duration of each play
+    pub fn with_yoyo(mut self) -> Self {
+        self.yoyo = true;
+        self
+    }
+
+    /// Plays again `count` more times, the delay is only waited once
+    pub fn with_repeat(mut self, count: u32) -> Self {
+        self.repetitions.count = Some(count);
+        self
+    }
+
+    pub fn with_repeat_forever(mut self) -> Self {
+        self.repetitions.count = None;
+        self
+    }
+
+    /// Called once when the tween finishes
+    pub fn with_on_complete(mut self, on_complete: impl FnMut(&mut T) + 'static) -> Self {
+        self.on_complete = Some(Box::new(on_complete));
+        self
+    }
+
+    /// Seconds of a play, including the way back for a yoyo
+    fn play_duration(&self) -> f32 {
+        if self.yoyo { self.duration * 2.0 } else { self.duration }
+    }
+
+    fn value_at(&self, from: V, time: f32) -> V {
+        let mut progress = if self.duration > 0.0 { time / self.duration } else { 1.0 };
+        if progress > 1.0 {
+            progress = 2.0 - progress;
+        }
+        V::interpolate(from, self.to, self.easing.apply(progress))
+    }
+}
+
+impl<T: ?Sized, V: Tweenable> Animation<T> for Tween<T, V> {
+    fn update(&mut self, delta_time: f32, target: &mut T) -> f32 {
+        if self.finished {
+            return delta_time;
+        }
+
+        let waited = delta_time.min(self.delay_left);
+        self.delay_left -= waited;
+        if self.delay_left > 0.0 {
+            return 0.0;
+        }
+
+        let get = self.get;
+        let from = *self.from.get_or_insert_with(|| get(target));
+        let play_duration = self.play_duration();
+        self.time += delta_time - waited;
+
+        while self.time >= play_duration {
+            if play_duration > 0.0 && self.repetitions.next() {
+                self.time -= play_duration;
+                continue;
+            }
+
+            (self.set)(target, if self.yoyo { from } else { self.to });
+            // A zero duration tween repeated forever is applied once per update
+            if play_duration == 0.0 && self.repetitions.count.is_none() {
+                return 0.0;
+            }
+
+            let left = self.time - play_duration;
+            self.time = play_duration;
+            self.finished = true;
+            if let Some(on_complete) = self.on_complete.as_mut() {
+                on_complete(target);
+            }
+            return left;
+        }
+
+        (self.set)(target, self.value_at(from, self.time));
+        0.0
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn restart(&mut self) {
+        self.delay_left = self.delay;
+        self.time = 0.0;
+        self.repetitions.done = 0;
+        self.finished = false;
+    }
+}
+
+impl<T: Transformable> Tween<T, Vector2f> {
+    pub fn position(to: impl Into<Vector2f>, duration: f32) -> Self {
+        Self::new(|target| target.position(), |target, position| target.set_position(position), to.into(), duration)
+    }
+
+    pub fn scale(to: impl Into<Vector2f>, duration: f32) -> Self {
+        Self::new(|target| target.get_scale(), |target, scale| target.set_scale(scale), to.into(), duration)
+    }
+
+    pub fn origin(to: impl Into<Vector2f>, duration: f32) -> Self {
+        Self::new(|target| target.origin(), |target, origin| target.set_origin(origin), to.into(), duration)
+    }
+}
+
+impl<T: Transformable> Tween<T, Angle> {
+    /// Turns the shortest way to the rotation in degrees, use `Tween::new` with a f32 to spin
+    pub fn rotation(to: f32, duration: f32) -> Self {
+        Self::new(|target| Angle(target.rotation()), |target, angle| target.set_rotation(angle.0), Angle(to), duration)
+    }
+}
+
+impl<T: TweenColor + ?Sized> Tween<T, Color> {
+    pub fn color(to: Color, duration: f32) -> Self {
+        Self::new(|target| target.tween_color(), |target, color| target.set_tween_color(color), to, duration)
+    }
+}
+
+impl<T: TweenColor + ?Sized> Tween<T, f32> {
+    /// Animates the alpha of the color, from 0 to 255
+    pub fn alpha(to: f32, duration: f32) -> Self {
+        Self::new(
+            |target| target.tween_color().a as f32,
+            |target, alpha| {
+                let mut color = target.tween_color();
+                color.a = alpha.round().clamp(0.0, 255.0) as u8;
+                target.set_tween_color(color);
+            },
+            to,
+            duration
+        )
+    }
+}
+
+impl<T: Widget + ?Sized> Tween<T, Vector2f> {
+    pub fn widget_position(to: impl Into<Vector2f>, duration: f32) -> Self {
+        Self::new(|target| target.position(), |target, position| target.set_position(position), to.into(), duration)
+    }
+
+    pub fn widget_size(to: impl Into<Vector2f>, duration: f32) -> Self {
+        Self::new(|target| target.size(), |target, size| target.set_size(size), to.into(), duration)
+    }
+}
+
+//////////////// Groups ////////////////////////
+
+/// Waits before the next animation of a sequence
+pub struct Delay {
+    duration: f32,
+    elapsed: f32
+}
+
+impl Delay {
+    pub fn new(duration: f32) -> Self {
+        Self {
+            duration: duration.max(0.0),
+            elapsed: 0.0
+        }
+    }
+}
+
+impl<T: ?Sized> Animation<T> for Delay {
+    fn update(&mut self, delta_time: f32, _target: &mut T) -> f32 {
+        let left = (self.elapsed + delta_time - self.duration).max(0.0);
+        self.elapsed = (self.elapsed + delta_time).min(self.duration);
+        left
+    }
+
+    fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    fn restart(&mut self) {
+        self.elapsed = 0.0;
+    }
+}
+
+/// Calls a function on the target, to run code at a point of a sequence
+struct Call<T: ?Sized> {
+    call: Callback<T>,
+    done: bool
+}
+
+impl<T: ?Sized> Animation<T> for Call<T> {
+    fn update(&mut self, delta_time: f32, target: &mut T) -> f32 {
+        if !self.done {
+            self.done = true;
+            (self.call)(target);
+        }
+        delta_time
+    }
+
+    fn is_finished(&self) -> bool {
+        self.done
+    }
+
+    fn restart(&mut self) {
+        self.done = false;
+    }
+}
+
+/// Animations played one after the other
+pub struct Sequence<T: ?Sized> {
+    animations: Vec<Box<dyn Animation<T>>>,
+    current: usize,
+    repetitions: Repetitions,
+    on_complete: Option<Callback<T>>,
+    finished: bool
+}
+
+impl<T: ?Sized + 'static> Default for Sequence<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized + 'static> Sequence<T> {
+    pub fn new() -> Self {
+        Self {
+            animations: Vec::new(),
+            current: 0,
+            repetitions: Repetitions::new(),
+            on_complete: None,
+            finished: false
+        }
+    }
+
+    pub fn then(mut self, animation: impl Animation<T> + 'static) -> Self {
+        self.animations.push(Box::new(animation));
+        self
+    }
+
+    pub fn then_wait(self, duration: f32) -> Self {
+        self.then(Delay::new(duration))
+    }
+
+    pub fn then_call(self, call: impl FnMut(&mut T) + 'static) -> Self {
+        self.then(Call {
+            call: Box::new(call),
+            done: false
+        })
+    }
+
+    /// Plays the whole sequence again `count` more times
+    pub fn with_repeat(mut self, count: u32) -> Self {
+        self.repetitions.count = Some(count);
+        self
+    }
+
+    pub fn with_repeat_forever(mut self) -> Self {
+        self.repetitions.count = None;
+        self
+    }
+
+    pub fn with_on_complete(mut self, on_complete: impl FnMut(&mut T) + 'static) -> Self {
+        self.on_complete = Some(Box::new(on_complete));
+        self
+    }
+}
+
+impl<T: ?Sized> Animation<T> for Sequence<T> {
+    fn update(&mut self, delta_time: f32, target: &mut T) -> f32 {
+        if self.finished {
+            return delta_time;
+        }
+
+        let mut time = delta_time;
+        let mut play_start_time = if self.current == 0 { Some(time) } else { None };
+        loop {
+            if let Some(animation) = self.animations.get_mut(self.current) {
+                time = animation.update(time, target);
+                if !animation.is_finished() {
+                    return 0.0;
+                }
+                self.current += 1;
+                continue;
+            }
+
+            // A play taking no time is not repeated in the same update
+            let instant_play = play_start_time == Some(time);
+            if self.repetitions.next() {
+                self.current = 0;
+                self.animations.iter_mut().for_each(|animation| animation.restart());
+                if instant_play {
+                    return 0.0;
+                }
+                play_start_time = Some(time);
+                continue;
+            }
+
+            self.finished = true;
+            if let Some(on_complete) = self.on_complete.as_mut() {
+                on_complete(target);
+            }
+            return time;
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn restart(&mut self) {
+        self.current = 0;
+        self.repetitions.done = 0;
+        self.finished = false;
+        self.animations.iter_mut().for_each(|animation| animation.restart());
+    }
+}
+
+/// Animations played at the same time, finished when all of them are
+pub struct Parallel<T: ?Sized> {
+    animations: Vec<Box<dyn Animation<T>>>,
+    repetitions: Repetitions,
+    on_complete: Option<Callback<T>>,
+    finished: bool
+}
+
+impl<T: ?Sized + 'static> Default for Parallel<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized + 'static> Parallel<T> {
+    pub fn new() -> Self {
+        Self {
+            animations: Vec::new(),
+            repetitions: Repetitions::new(),
+            on_complete: None,
+            finished: false
+        }
+    }
+
+    pub fn with(mut self, animation: impl Animation<T> + 'static) -> Self {
+        self.animations.push(Box::new(animation));
+        self
+    }
+
+    /// Plays the whole group again `count` more times
+    pub fn with_repeat(mut self, count: u32) -> Self {
+        self.repetitions.count = Some(count);
+        self
+    }
+
+    pub fn with_repeat_forever(mut self) -> Self {
+        self.repetitions.count = None;
+        self
+    }
+
+    pub fn with_on_complete(mut self, on_complete: impl FnMut(&mut T) + 'static) -> Self {
+        self.on_complete = Some(Box::new(on_complete));
+        self
+    }
+}
+
+impl<T: ?Sized> Animation<T> for Parallel<T> {
+    fn update(&mut self, delta_time: f32, target: &mut T) -> f32 {
+        if self.finished {
+            return delta_time;
+        }
+
+        let mut time = delta_time;
+        loop {
+            // The group finishes with the last of its animations, the one leaving the least time
+            let left = self.animations.iter_mut()
+                .map(|animation| animation.update(time, target))
+                .fold(time, f32::min);
+            if !self.animations.iter().all(|animation| animation.is_finished()) {
+                return 0.0;
+            }
+
+            let instant_play = left == time;
+            if self.repetitions.next() {
+                self.animations.iter_mut().for_each(|animation| animation.restart());
+                if instant_play {
+                    return 0.0;
+                }
+                time = left;
+                continue;
+            }
+
+            self.finished = true;
+            if let Some(on_complete) = self.on_complete.as_mut() {
+                on_complete(target);
+            }
+            return left;
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn restart(&mut self) {
+        self.repetitions.done = 0;
+        self.finished = false;
+        self.animations.iter_mut().for_each(|animation| animation.restart());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 31] = [
+        Easing::Linear,
+        Easing::QuadIn, Easing::QuadOut, Easing::QuadInOut,
+        Easing::CubicIn, Easing::CubicOut, Easing::CubicInOut,
+        Easing::QuartIn, Easing::QuartOut, Easing::QuartInOut,
+        Easing::QuintIn, Easing::QuintOut, Easing::QuintInOut,
+        Easing::SineIn, Easing::SineOut, Easing::SineInOut,
+        Easing::ExpoIn, Easing::ExpoOut, Easing::ExpoInOut,
+        Easing::CircIn, Easing::CircOut, Easing::CircInOut,
+        Easing::BackIn, Easing::BackOut, Easing::BackInOut,
+        Easing::ElasticIn, Easing::ElasticOut, Easing::ElasticInOut,
+        Easing::BounceIn, Easing::BounceOut, Easing::BounceInOut
+    ];
+
+    fn assert_near(value: f32, expected: f32) {
+        assert!((value - expected).abs() < 1e-3, "expected {}, got {}", expected, value);
+    }
+
+    fn value_tween(to: f32, duration: f32) -> Tween<f32, f32> {
+        Tween::new(|value| *value, |value, new_value| *value = new_value, to, duration)
+    }
+
+    #[test]
+    fn easings_start_at_0_and_end_at_1() {
+        for easing in EASINGS {
+            assert_near(easing.apply(0.0), 0.0);
+            assert_near(easing.apply(1.0), 1.0);
+            // The progress is clamped
+            assert_near(easing.apply(-1.0), 0.0);
+            assert_near(easing.apply(2.0), 1.0);
+        }
+        for easing in [Easing::QuadInOut, Easing::CubicInOut, Easing::SineInOut, Easing::BackInOut, Easing::BounceInOut] {
+            assert_near(easing.apply(0.5), 0.5);
+        }
+    }
+
+    #[test]
+    fn tween_returns_the_time_left_when_it_finishes() {
+        let mut value = 0.0;
+        let mut tween = value_tween(10.0, 1.0).with_delay(0.5);
+
+        assert_eq!(tween.update(0.75, &mut value), 0.0);
+        assert_near(value, 2.5);
+        assert_near(tween.update(1.0, &mut value), 0.25);
+        assert_eq!(value, 10.0);
+        assert!(tween.is_finished());
+    }
+
+    #[test]
+    fn yoyo_tween_goes_back_to_its_start() {
+        let mut value = 0.0;
+        let mut tween = value_tween(10.0, 1.0).with_yoyo().with_repeat(1);
+
+        tween.update(1.5, &mut value);
+        assert_near(value, 5.0);
+        tween.update(1.0, &mut value);
+        assert_near(value, 5.0);
+        assert!(!tween.is_finished());
+        assert_near(tween.update(2.0, &mut value), 0.5);
+        assert_eq!(value, 0.0);
+    }
+
+    #[test]
+    fn sequence_passes_the_time_left_to_the_next_animation() {
+        let mut value = 0.0;
+        let mut sequence = Sequence::new()
+            .then(value_tween(10.0, 1.0))
+            .then_wait(0.5)
+            .then(value_tween(20.0, 1.0));
+
+        // The first tween finishes and the delay starts with the remaining 0.25
+        sequence.update(1.25, &mut value);
+        assert_eq!(value, 10.0);
+        // 0.25 ends the delay, the second tween gets 0.5
+        sequence.update(0.75, &mut value);
+        assert_near(value, 15.0);
+        assert!(!sequence.is_finished());
+
+        assert_near(sequence.update(1.0, &mut value), 0.5);
+        assert_eq!(value, 20.0);
+        assert!(sequence.is_finished());
+    }
+
+    #[test]
+    fn repeated_sequence_restarts_with_the_time_left() {
+        let mut value = 0.0;
+        let calls = std::rc::Rc::new(std::cell::Cell::new(0));
+        let counter = calls.clone();
+        let mut sequence = Sequence::new()
+            .then(value_tween(10.0, 1.0).with_from(0.0))
+            .then_call(move |_| counter.set(counter.get() + 1))
+            .with_repeat(1);
+
+        sequence.update(1.5, &mut value);
+        assert_near(value, 5.0);
+        assert_eq!(calls.get(), 1);
+        assert_near(sequence.update(1.0, &mut value), 0.5);
+        assert_eq!(value, 10.0);
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn parallel_finishes_with_its_longest_animation() {
+        let mut value = 0.0;
+        let mut parallel = Parallel::new()
+            .with(value_tween(10.0, 1.0))
+            .with(Delay::new(2.0));
+
+        assert_eq!(parallel.update(1.5, &mut value), 0.0);
+        assert_eq!(value, 10.0);
+        assert_near(parallel.update(1.0, &mut value), 0.5);
+        assert!(parallel.is_finished());
+    }
+}
@@ -1,7 +1,10 @@
 
 
+use std::cell::{Cell, RefCell};
 use crate::sfml_export::*;
-use crate::rendering::get_quad;
+use crate::rendering::{get_quad_corners, QuadMesh};
+use crate::core::assets_manager::FontAsset;
+use crate::core::localization::{Localization, TextKey};
 
 #[derive(Default)]
 struct TextTransform {
@@ -25,11 +28,16 @@ impl TextTransform {
 
 pub struct DynamicText {
     text: String,
-    font: Option<SfBox<Font>>,
+    font: Option<FontAsset>,
     char_size: u32,
     transform: TextTransform,
     color: Color,
-    size: Vector2f
+    size: Vector2f,
+    text_key: Option<TextKey>,
+    localized_revision: Option<u32>,
+    /// Glyphs quads, generated again on the next draw when the text changed
+    mesh: RefCell<QuadMesh>,
+    mesh_dirty: Cell<bool>
 }
 
 impl DynamicText {
@@ -40,12 +48,17 @@ impl DynamicText {
             char_size: 30,
             transform: TextTransform::default(),
             color: Color::WHITE,
-            size: Vector2f::default()
+            size: Vector2f::default(),
+            text_key: None,
+            localized_revision: None,
+            mesh: RefCell::new(QuadMesh::new()),
+            mesh_dirty: Cell::new(true)
         }
     }
 
     pub fn set_size(&mut self, size: impl Into<Vector2f>) {
         self.size = size.into();
+        self.invalidate();
     }
 
     pub fn size(&self) -> Vector2f {
@@ -54,22 +67,52 @@ impl DynamicText {
 
     pub fn set_color(&mut self, color: Color) {
         self.color = color;
+        self.invalidate();
     }
 
     pub fn color(&self) -> Color {
         self.color
     }
 
-    pub fn set_font(&mut self, font: &SfBox<Font>) {
+    pub fn set_font(&mut self, font: &FontAsset) {
         self.font = Some(font.clone());
+        self.invalidate();
     }
 
     pub fn set_string(&mut self, string: &str) {
         self.text = string.to_string();
+        self.invalidate();
+    }
+
+    /// The string is replaced by the localized one on the next `localize`
+    pub fn bind_key(&mut self, text_key: TextKey) {
+        self.text_key = Some(text_key);
+        self.localized_revision = None;
+    }
+
+    pub fn unbind_key(&mut self) {
+        self.text_key = None;
+    }
+
+    /// Updates the string of the bound key if the localization changed, returns true if it did
+    pub fn localize(&mut self, localization: &Localization) -> bool {
+        let text_key = match &self.text_key {
+            Some(text_key) => text_key,
+            None => return false
+        };
+
+        if self.localized_revision == Some(localization.revision()) {
+            return false;
+        }
+        self.text = localization.format(text_key);
+        self.localized_revision = Some(localization.revision());
+        self.invalidate();
+        true
     }
 
     pub fn set_char_size(&mut self, char_size: u32) {
         self.char_size = char_size;
+        self.invalidate();
     }
 
     pub fn global_bounds(&self) -> FloatRect {
@@ -128,6 +171,11 @@ impl DynamicText {
         transform.scale_with_center(self.transform.scale.x, self.transform.scale.y, self.transform.origin.x, self.transform.origin.y);
         self.transform.transform = transform;
         self.transform.inverse_transform = transform.inverse();
+        self.invalidate();
+    }
+
+    fn invalidate(&mut self) {
+        self.mesh_dirty.set(true);
     }
 }
 
@@ -171,7 +219,8 @@ impl Transformable for DynamicText {
     }
 
     fn set_origin<O: Into<Vector2f>>(&mut self, origin: O) {
-        self.transform.origin = origin.into()
+        self.transform.origin = origin.into();
+        self.invalidate();
     }
 
     fn set_position<P: Into<Vector2f>>(&mut self, position: P) {
@@ -206,16 +255,19 @@ impl Drawable for DynamicText {
         ) {
         let mut states = states.clone();
         states.set_texture(Some(self.font.as_ref().unwrap().texture(self.char_size)));
-        let vertices = 
-            gen_glyphs_vertices(self);
-        target.draw_primitives(&vertices, PrimitiveType::TRIANGLES, &states);
+
+        let mut mesh = self.mesh.borrow_mut();
+        if self.mesh_dirty.replace(false) {
+            mesh.clear();
+            gen_glyphs_quads(self, &mut mesh);
+        }
+        mesh.render(target, &states);
     }
 }
 
 
 
-fn gen_glyphs_vertices(dynamic_text: &DynamicText) -> Vec<Vertex>{
-    let mut vertices = Vec::new();
+fn gen_glyphs_quads(dynamic_text: &DynamicText, mesh: &mut QuadMesh) {
     let mut advance = 0.0;
     let mut nb_lines = 0.0;
 
@@ -273,10 +325,7 @@ fn gen_glyphs_vertices(dynamic_text: &DynamicText) -> Vec<Vertex>{
         glyph_trans.rotate_with_center(dynamic_text.rotation(), transform.origin.x, transform.origin.y);
 
 
-        let quad = get_quad(&glyph_trans, color, texture_rect);
+        mesh.push_corners(get_quad_corners(&glyph_trans, color, texture_rect));
         advance += glyph.advance();
-
-        vertices.extend_from_slice(&quad);
     }
-    vertices
 }
\ No newline at end of file
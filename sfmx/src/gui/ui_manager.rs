@@ -1,6 +1,7 @@
 use super::widgets::Widget;
 use std::{collections::HashMap, hash::Hash, any::Any};
 use crate::sfml_export::*;
+use crate::core::localization::Localization;
 
 pub trait UIWidget: Widget + Any {
     fn as_any(&self) -> &dyn Any;
@@ -70,6 +71,12 @@ impl<IDType: Eq + Hash> UiManager<IDType> {
         }
     }
 
+    pub fn localize(&mut self, localization: &Localization) {
+        for widget in self.widgets.values_mut() {
+            widget.localize(localization);
+        }
+    }
+
     pub fn reset(&mut self) {
         for widget in self.widgets.values_mut() {
             widget.reset();
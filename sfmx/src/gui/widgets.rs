@@ -1,4 +1,6 @@
 use crate::{sfml_export::*, prelude::DynamicText};
+use crate::core::assets_manager::FontAsset;
+use crate::core::localization::{Localization, TextKey};
 
 pub trait Widget {
     fn position(&self) -> Vector2f;
@@ -14,6 +16,9 @@ pub trait Widget {
     fn reset(&mut self);
 
     fn draw(&self, target: &mut dyn RenderTarget);
+
+    /// Refreshes the texts bound to a localization key
+    fn localize(&mut self, _localization: &Localization) {}
 }
 
 pub struct Button {
@@ -63,7 +68,12 @@ impl Button {
         self.text.set_char_size(char_size);
     }
 
-    pub fn set_font(&mut self, font: &SfBox<Font>) {
+    /// The text follows the language, the widgets are refreshed with `UiManager::localize`
+    pub fn bind_key(&mut self, text_key: TextKey) {
+        self.text.bind_key(text_key);
+    }
+
+    pub fn set_font(&mut self, font: &FontAsset) {
         self.text.set_font(font);
     }
 
@@ -128,6 +138,12 @@ impl Widget for Button {
         self.size
     }
 
+    fn localize(&mut self, localization: &Localization) {
+        if self.text.localize(localization) {
+            self.update_text();
+        }
+    }
+
     fn reset(&mut self) {
         let bounds = FloatRect::from_vecs(self.position, self.size);
         self.clicked = false;
@@ -151,4 +167,95 @@ impl Widget for Button {
             _ => {}
         }
     }
-}
\ No newline at end of file
+}
+
+pub struct ProgressBar {
+    position: Vector2f,
+    size: Vector2f,
+    /// Between 0 and 1
+    progress: f32,
+
+    background_color: Color,
+    fill_color: Color,
+    outline_color: Color,
+    outline_thickness: f32
+}
+
+impl ProgressBar {
+    pub fn new() -> Box<Self> {
+        Box::new(
+            Self {
+                position: Vector2f::default(),
+                size: Vector2f::default(),
+                progress: 0.0,
+
+                background_color: Color::rgb(40, 40, 40),
+                fill_color: Color::WHITE,
+                outline_color: Color::WHITE,
+                outline_thickness: 2.0
+            }
+        )
+    }
+
+    pub fn set_progress(&mut self, progress: f32) {
+        self.progress = progress.clamp(0.0, 1.0);
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+
+    pub fn set_colors(&mut self, background_color: Color, fill_color: Color) {
+        self.background_color = background_color;
+        self.fill_color = fill_color;
+    }
+
+    pub fn set_outline(&mut self, outline_color: Color, outline_thickness: f32) {
+        self.outline_color = outline_color;
+        self.outline_thickness = outline_thickness;
+    }
+}
+
+impl Widget for ProgressBar {
+    fn draw(&self, target: &mut dyn RenderTarget) {
+        let mut background = RectangleShape::with_size(self.size);
+        background.set_position(self.position);
+        background.set_fill_color(self.background_color);
+        background.set_outline_color(self.outline_color);
+        background.set_outline_thickness(self.outline_thickness);
+        target.draw(&background);
+
+        let mut fill = RectangleShape::with_size(Vector2f::new(self.size.x * self.progress, self.size.y));
+        fill.set_position(self.position);
+        fill.set_fill_color(self.fill_color);
+        target.draw(&fill);
+    }
+
+    fn is_clicked(&self) -> bool {
+        false
+    }
+
+    fn is_hovered(&self) -> bool {
+        false
+    }
+
+    fn position(&self) -> Vector2f {
+        self.position
+    }
+
+    fn set_position(&mut self, position: Vector2f) {
+        self.position = position;
+    }
+
+    fn set_size(&mut self, size: Vector2f) {
+        self.size = size;
+    }
+
+    fn size(&self) -> Vector2f {
+        self.size
+    }
+
+    fn reset(&mut self) {}
+
+    fn on_event(&mut self, _event: Event) {}
+}
@@ -3,4 +3,5 @@ mod sfml_export;
 pub mod prelude;
 pub mod core;
 pub mod gui;
-pub mod rendering;
\ No newline at end of file
+pub mod rendering;
+pub mod states;
\ No newline at end of file
@@ -1,3 +1,5 @@
 pub use crate::sfml_export::*;
 pub use crate::core::*;
-pub use crate::gui::*;
\ No newline at end of file
+pub use crate::gui::*;
+pub use crate::rendering::*;
+pub use crate::states::*;
\ No newline at end of file
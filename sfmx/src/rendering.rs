@@ -1,29 +1,135 @@
+pub mod animated_sprite;
+pub mod atlas;
+pub mod camera;
+pub mod frame_capture;
+pub mod particles;
+pub mod post_process;
+pub mod quad_mesh;
+pub mod render_queue;
+pub mod shapes;
+pub mod sprite_batch;
+
+pub use animated_sprite::*;
+pub use atlas::*;
+pub use camera::*;
+pub use frame_capture::*;
+pub use particles::*;
+pub use post_process::*;
+pub use quad_mesh::*;
+pub use render_queue::*;
+pub use shapes::*;
+pub use sprite_batch::*;
+
 use crate::sfml_export::*;
 
-pub fn get_quad(transform: &Transform, color: Color, tex_coords: FloatRect) -> [Vertex; 6] {
-    let mut v = [Vertex::default(); 6];
+/// Order of the corners of `get_quad_corners` forming the two triangles of a quad
+pub const QUAD_INDICES: [usize; 6] = [0, 1, 2, 2, 3, 0];
 
-    let bounds = transform.transform_rect(FloatRect::new(0.0, 0.0, 1.0, 1.0));
+/// Top left, bottom left, bottom right and top right corners of the transformed unit rect
+///
+/// Each corner is transformed on its own so rotations and shears are kept.
+pub fn get_quad_corners(transform: &Transform, color: Color, tex_coords: FloatRect) -> [Vertex; 4] {
+    let mut v = [Vertex::default(); 4];
 
-    v[0].position = Vector2f::new(bounds.left, bounds.top);
+    v[0].position = transform.transform_point(Vector2f::new(0.0, 0.0));
     v[0].color = color;
     v[0].tex_coords = Vector2f::new(tex_coords.left, tex_coords.top);
 
-    v[1].position = Vector2f::new(bounds.left, bounds.top + bounds.height);
+    v[1].position = transform.transform_point(Vector2f::new(0.0, 1.0));
     v[1].color = color;
     v[1].tex_coords = Vector2f::new(tex_coords.left, tex_coords.top + tex_coords.height);
 
-    v[2].position = Vector2f::new(bounds.left + bounds.width, bounds.top + bounds.height);
+    v[2].position = transform.transform_point(Vector2f::new(1.0, 1.0));
     v[2].color = color;
     v[2].tex_coords = Vector2f::new(tex_coords.left + tex_coords.width, tex_coords.top + tex_coords.height);
 
-    v[3] = v[2];
+    v[3].position = transform.transform_point(Vector2f::new(1.0, 0.0));
+    v[3].color = color;
+    v[3].tex_coords = Vector2f::new(tex_coords.left + tex_coords.width, tex_coords.top);
 
-    v[4].position = Vector2f::new(bounds.left + bounds.width, bounds.top);
-    v[4].color = color;
-    v[4].tex_coords = Vector2f::new(tex_coords.left + tex_coords.width, tex_coords.top);
+    v
+}
 
-    v[5] = v[0];
+/// Two triangles of the transformed unit rect
+pub fn get_quad(transform: &Transform, color: Color, tex_coords: FloatRect) -> [Vertex; 6] {
+    let corners = get_quad_corners(transform, color, tex_coords);
+    QUAD_INDICES.map(|i| corners[i])
+}
 
-    v
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEX_COORDS: FloatRect = FloatRect::new(10.0, 20.0, 30.0, 40.0);
+
+    fn assert_positions(vertices: &[Vertex], expected: &[(f32, f32)]) {
+        assert_eq!(vertices.len(), expected.len());
+        for (vertex, (x, y)) in vertices.iter().zip(expected) {
+            let position = vertex.position;
+            assert!(
+                (position.x - x).abs() < 1e-4 && (position.y - y).abs() < 1e-4,
+                "expected ({}, {}), got ({}, {})", x, y, position.x, position.y
+            );
+        }
+    }
+
+    #[test]
+    fn scaled_and_translated_quad() {
+        // Scale by (4, 2) then translate by (10, 5)
+        let transform = Transform::new(
+            4.0, 0.0, 10.0,
+            0.0, 2.0, 5.0,
+            0.0, 0.0, 1.0
+        );
+        let quad = get_quad(&transform, Color::WHITE, TEX_COORDS);
+        assert_positions(&quad, &[(10.0, 5.0), (10.0, 7.0), (14.0, 7.0), (14.0, 7.0), (14.0, 5.0), (10.0, 5.0)]);
+    }
+
+    #[test]
+    fn rotated_quad_is_not_an_axis_aligned_box() {
+        // 90 degrees clockwise on screen, x goes down and y goes left
+        let transform = Transform::new(
+            0.0, -1.0, 0.0,
+            1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0
+        );
+        let corners = get_quad_corners(&transform, Color::WHITE, TEX_COORDS);
+        assert_positions(&corners, &[(0.0, 0.0), (-1.0, 0.0), (-1.0, 1.0), (0.0, 1.0)]);
+
+        // 45 degrees, the quad is a diamond and no corner is on its bounding box corners
+        let (sin, cos) = 45f32.to_radians().sin_cos();
+        let transform = Transform::new(
+            2.0 * cos, -2.0 * sin, 5.0,
+            2.0 * sin, 2.0 * cos, 5.0,
+            0.0, 0.0, 1.0
+        );
+        let corners = get_quad_corners(&transform, Color::WHITE, TEX_COORDS);
+        let half_diagonal = 2.0 * std::f32::consts::SQRT_2 / 2.0;
+        assert_positions(&corners, &[
+            (5.0, 5.0),
+            (5.0 - half_diagonal, 5.0 + half_diagonal),
+            (5.0, 5.0 + 2.0 * half_diagonal),
+            (5.0 + half_diagonal, 5.0 + half_diagonal)
+        ]);
+    }
+
+    #[test]
+    fn sheared_quad() {
+        let transform = Transform::new(
+            1.0, 0.5, 0.0,
+            0.0, 1.0, 0.0,
+            0.0, 0.0, 1.0
+        );
+        let corners = get_quad_corners(&transform, Color::WHITE, TEX_COORDS);
+        assert_positions(&corners, &[(0.0, 0.0), (0.5, 1.0), (1.5, 1.0), (1.0, 0.0)]);
+    }
+
+    #[test]
+    fn quad_keeps_colors_and_tex_coords() {
+        let color = Color::rgb(1, 2, 3);
+        let quad = get_quad(&Transform::IDENTITY, color, TEX_COORDS);
+        let tex_coords = quad.map(|vertex| (vertex.tex_coords.x, vertex.tex_coords.y));
+        assert_eq!(tex_coords, [(10.0, 20.0), (10.0, 60.0), (40.0, 60.0), (40.0, 60.0), (40.0, 20.0), (10.0, 20.0)]);
+        assert!(quad.iter().all(|vertex| vertex.color == color));
+    }
+}
@@ -0,0 +1,509 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use serde_json::Value;
+use crate::sfml_export::*;
+use crate::core::file_system::FileSystem;
+
+/// Duration of the frames without one, in seconds
+const DEFAULT_FRAME_DURATION: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlayMode {
+    Loop,
+    /// Plays forward then backward
+    PingPong,
+    /// Stops on the last frame
+    OneShot
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AnimationFrame {
+    pub rect: IntRect,
+    /// Seconds
+    pub duration: f32
+}
+
+/// Frames of a sprite sheet played in order
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationClip {
+    pub frames: Vec<AnimationFrame>,
+    pub mode: PlayMode
+}
+
+//////////////// Sprite sheet ////////////////////////
+
+/// Frames of a texture and the named clips made of them
+#[derive(Clone, Debug, Default)]
+pub struct SpriteSheet {
+    frames: Vec<AnimationFrame>,
+    frame_names: HashMap<String, usize>,
+    clips: HashMap<String, AnimationClip>
+}
+
+impl SpriteSheet {
+    pub fn new() -> Self {
+        Self {
+            frames: Vec::new(),
+            frame_names: HashMap::new(),
+            clips: HashMap::new()
+        }
+    }
+
+    /// Frames of a grid read row by row, all with the same duration
+    pub fn from_grid(frame_size: Vector2u, columns: u32, rows: u32, frame_duration: f32) -> Self {
+        let mut res = Self::new();
+        for row in 0..rows {
+            for column in 0..columns {
+                res.add_frame(AnimationFrame {
+                    rect: IntRect::new(
+                        (column * frame_size.x) as i32,
+                        (row * frame_size.y) as i32,
+                        frame_size.x as i32,
+                        frame_size.y as i32
+                    ),
+                    duration: frame_duration
+                });
+            }
+        }
+        res
+    }
+
+    /// Aseprite or TexturePacker json export
+    ///
+    /// The frames can be an array or a map of named frames. The clips are the Aseprite
+    /// frame tags, or the `animations` lists of frames names of TexturePacker.
+    pub fn parse_json(content: &str) -> Result<Self, String> {
+        let json = serde_json::from_str::<Value>(content).map_err(|e| format!("Invalid sprite sheet: {}", e))?;
+        let mut res = Self::new();
+
+        let frames = match &json["frames"] {
+            Value::Array(frames) => frames.iter().map(|frame| (frame["filename"].as_str(), frame)).collect::<Vec<_>>(),
+            Value::Object(frames) => frames.iter().map(|(name, frame)| (Some(name.as_str()), frame)).collect(),
+            _ => return Err("Sprite sheet has no frames".to_string())
+        };
+
+        for (name, frame) in frames {
+            let rect = &frame["frame"];
+            let value = |key: &str| rect[key].as_i64().map(|v| v as i32).ok_or(format!("Frame has no {}", key));
+            let index = res.add_frame(AnimationFrame {
+                rect: IntRect::new(value("x")?, value("y")?, value("w")?, value("h")?),
+                duration: frame["duration"].as_f64().map_or(DEFAULT_FRAME_DURATION, |ms| ms as f32 / 1000.0)
+            });
+            if let Some(name) = name {
+                res.frame_names.insert(name.to_string(), index);
+            }
+        }
+
+        // Aseprite
+        for tag in json["meta"]["frameTags"].as_array().into_iter().flatten() {
+            let name = tag["name"].as_str().ok_or("Frame tag has no name")?;
+            let from = tag["from"].as_u64().ok_or(format!("Frame tag {} has no start", name))? as usize;
+            let to = tag["to"].as_u64().ok_or(format!("Frame tag {} has no end", name))? as usize;
+            let mut frames = (from..=to).collect::<Vec<_>>();
+            let mode = match tag["direction"].as_str().unwrap_or("forward") {
+                "reverse" => {
+                    frames.reverse();
+                    PlayMode::Loop
+                },
+                "pingpong" => PlayMode::PingPong,
+                "pingpong_reverse" => {
+                    frames.reverse();
+                    PlayMode::PingPong
+                },
+                _ => PlayMode::Loop
+            };
+            res.add_clip(name, &frames, mode)?;
+        }
+
+        // TexturePacker
+        for (name, frames_names) in json["animations"].as_object().into_iter().flatten() {
+            let frames = frames_names.as_array().into_iter().flatten()
+                .map(|frame_name| {
+                    let frame_name = frame_name.as_str().unwrap_or_default();
+                    res.frame_index(frame_name).ok_or(format!("Animation {} refers to unknown frame {}", name, frame_name))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            res.add_clip(name, &frames, PlayMode::Loop)?;
+        }
+
+        Ok(res)
+    }
+
+    pub fn load(file_system: &FileSystem, path: &str) -> Result<Self, String> {
+        Self::parse_json(&file_system.read_to_string(path)?).map_err(|e| format!("{} in {}", e, path))
+    }
+
+    pub fn add_frame(&mut self, frame: AnimationFrame) -> usize {
+        self.frames.push(frame);
+        self.frames.len() - 1
+    }
+
+    /// Clip of the frames at the indices, in this order
+    pub fn add_clip(&mut self, name: &str, frames: &[usize], mode: PlayMode) -> Result<(), String> {
+        let frames = frames.iter()
+            .map(|index| self.frames.get(*index).copied().ok_or(format!("Clip {} refers to missing frame {}", name, index)))
+            .collect::<Result<Vec<_>, String>>()?;
+        if frames.is_empty() {
+            return Err(format!("Clip {} has no frame", name));
+        }
+
+        self.clips.insert(name.to_string(), AnimationClip {
+            frames,
+            mode
+        });
+        Ok(())
+    }
+
+    pub fn frames(&self) -> &[AnimationFrame] {
+        &self.frames
+    }
+
+    pub fn frame_index(&self, name: &str) -> Option<usize> {
+        self.frame_names.get(name).copied()
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&AnimationClip> {
+        self.clips.get(name)
+    }
+
+    pub fn clip_mut(&mut self, name: &str) -> Option<&mut AnimationClip> {
+        self.clips.get_mut(name)
+    }
+
+    pub fn clips(&self) -> &HashMap<String, AnimationClip> {
+        &self.clips
+    }
+}
+
+//////////////// Animated sprite ////////////////////////
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AnimationEvent {
+    FrameChanged { clip: String, frame: usize },
+    /// A looping clip started again, a ping-pong clip is back to its first frame
+    Looped { clip: String },
+    /// A one shot clip played its last frame
+    Finished { clip: String }
+}
+
+/// Position in the frames of a clip
+#[derive(Clone, Copy, Debug, Default)]
+struct Playback {
+    frame: usize,
+    backward: bool,
+    elapsed: f32,
+    finished: bool
+}
+
+impl Playback {
+    /// Advances by the time, returns what happened during this time
+    fn update(&mut self, name: &str, clip: &AnimationClip, time: f32) -> Vec<AnimationEvent> {
+        let mut events = Vec::new();
+        if self.finished {
+            return events;
+        }
+
+        self.elapsed += time;
+        let previous_frame = self.frame;
+        loop {
+            // A zero duration would never leave the loop
+            let duration = clip.frames[self.frame].duration.max(0.001);
+            if self.elapsed < duration {
+                break;
+            }
+            self.elapsed -= duration;
+
+            if let Some(event) = self.advance(name, clip) {
+                events.push(event);
+            }
+            if self.finished {
+                break;
+            }
+        }
+
+        if self.frame != previous_frame {
+            events.insert(0, AnimationEvent::FrameChanged {
+                clip: name.to_string(),
+                frame: self.frame
+            });
+        }
+        events
+    }
+
+    /// Moves to the next frame once the current one has been displayed for its duration
+    fn advance(&mut self, name: &str, clip: &AnimationClip) -> Option<AnimationEvent> {
+        let last = clip.frames.len() - 1;
+        match clip.mode {
+            PlayMode::Loop => {
+                if self.frame == last {
+                    self.frame = 0;
+                    return Some(AnimationEvent::Looped { clip: name.to_string() });
+                }
+                self.frame += 1;
+            },
+            PlayMode::PingPong => {
+                if last == 0 {
+                    return Some(AnimationEvent::Looped { clip: name.to_string() });
+                }
+                if self.frame == last {
+                    self.backward = true;
+                }
+                if self.backward {
+                    self.frame -= 1;
+                    if self.frame == 0 {
+                        self.backward = false;
+                        return Some(AnimationEvent::Looped { clip: name.to_string() });
+                    }
+                }
+                else {
+                    self.frame += 1;
+                }
+            },
+            PlayMode::OneShot => {
+                if self.frame == last {
+                    self.finished = true;
+                    return Some(AnimationEvent::Finished { clip: name.to_string() });
+                }
+                self.frame += 1;
+            }
+        }
+        None
+    }
+}
+
+/// Sprite playing the clips of a sprite sheet, advanced by `update` with `StateData::delta_time`
+pub struct AnimatedSprite {
+    sprite: RcSprite,
+    sheet: Rc<SpriteSheet>,
+    clip: Option<String>,
+    playback: Playback,
+    speed: f32,
+    paused: bool
+}
+
+impl AnimatedSprite {
+    pub fn new(texture: &RcTexture, sheet: Rc<SpriteSheet>) -> Self {
+        let mut sprite = RcSprite::with_texture(texture);
+        if let Some(frame) = sheet.frames().first() {
+            sprite.set_texture_rect(frame.rect);
+        }
+
+        Self {
+            sprite,
+            sheet,
+            clip: None,
+            playback: Playback::default(),
+            speed: 1.0,
+            paused: false
+        }
+    }
+
+    /// Starts the clip from its first frame, unless it is already playing. Returns false if it does not exist
+    pub fn play(&mut self, clip: &str) -> bool {
+        if self.clip.as_deref() == Some(clip) && !self.playback.finished {
+            self.paused = false;
+            return true;
+        }
+        self.play_from_start(clip)
+    }
+
+    pub fn play_from_start(&mut self, clip: &str) -> bool {
+        let rect = match self.sheet.clip(clip) {
+            Some(animation_clip) => animation_clip.frames[0].rect,
+            None => return false
+        };
+
+        self.clip = Some(clip.to_string());
+        self.playback = Playback::default();
+        self.paused = false;
+        self.sprite.set_texture_rect(rect);
+        true
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// True when a one shot clip displayed its last frame for its duration
+    pub fn is_finished(&self) -> bool {
+        self.playback.finished
+    }
+
+    /// Playback speed factor, 1 by default
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.0);
+    }
+
+    pub fn current_clip(&self) -> Option<&str> {
+        self.clip.as_deref()
+    }
+
+    /// Index of the frame in the current clip
+    pub fn current_frame(&self) -> usize {
+        self.playback.frame
+    }
+
+    /// Texture rect of the displayed frame, to draw it with a `SpriteBatch` for example
+    pub fn current_rect(&self) -> IntRect {
+        self.sprite.texture_rect()
+    }
+
+    pub fn sprite(&self) -> &RcSprite {
+        &self.sprite
+    }
+
+    /// To move, rotate or color the sprite
+    pub fn sprite_mut(&mut self) -> &mut RcSprite {
+        &mut self.sprite
+    }
+
+    /// Advances the current clip, returns what happened during this time
+    pub fn update(&mut self, delta_time: f32) -> Vec<AnimationEvent> {
+        if self.paused {
+            return Vec::new();
+        }
+
+        let sheet = self.sheet.clone();
+        let (name, clip) = match self.clip.as_deref().and_then(|name| Some((name, sheet.clip(name)?))) {
+            Some(clip) => clip,
+            None => return Vec::new()
+        };
+
+        let previous_frame = self.playback.frame;
+        let events = self.playback.update(name, clip, delta_time * self.speed);
+        if self.playback.frame != previous_frame {
+            self.sprite.set_texture_rect(clip.frames[self.playback.frame].rect);
+        }
+        events
+    }
+}
+
+impl Drawable for AnimatedSprite {
+    fn draw<'a: 'shader, 'texture, 'shader, 'shader_texture>(
+            &'a self,
+            target: &mut dyn RenderTarget,
+            states: &RenderStates<'texture, 'shader, 'shader_texture>,
+        ) {
+        target.draw_with_renderstates(&self.sprite, states);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip(mode: PlayMode) -> AnimationClip {
+        AnimationClip {
+            frames: (0..3).map(|i| AnimationFrame { rect: IntRect::new(i * 16, 0, 16, 16), duration: 1.0 }).collect(),
+            mode
+        }
+    }
+
+    fn frame_changed(frame: usize) -> AnimationEvent {
+        AnimationEvent::FrameChanged { clip: "clip".to_string(), frame }
+    }
+
+    fn looped() -> AnimationEvent {
+        AnimationEvent::Looped { clip: "clip".to_string() }
+    }
+
+    fn finished() -> AnimationEvent {
+        AnimationEvent::Finished { clip: "clip".to_string() }
+    }
+
+    #[test]
+    fn loop_clip_starts_again_after_its_last_frame() {
+        let clip = clip(PlayMode::Loop);
+        let mut playback = Playback::default();
+
+        assert_eq!(playback.update("clip", &clip, 0.5), vec![]);
+        assert_eq!(playback.update("clip", &clip, 2.0), vec![frame_changed(2)]);
+        assert_eq!(playback.update("clip", &clip, 0.25), vec![]);
+        assert_eq!(playback.update("clip", &clip, 0.25), vec![frame_changed(0), looped()]);
+        assert!(!playback.finished);
+    }
+
+    #[test]
+    fn ping_pong_clip_plays_backward_after_its_last_frame() {
+        let clip = clip(PlayMode::PingPong);
+        let mut playback = Playback::default();
+
+        let mut frames = Vec::new();
+        let mut loops = 0;
+        for _ in 0..6 {
+            let events = playback.update("clip", &clip, 1.0);
+            loops += events.iter().filter(|event| **event == looped()).count();
+            frames.push(playback.frame);
+        }
+        assert_eq!(frames, [1, 2, 1, 0, 1, 2]);
+        assert_eq!(loops, 1);
+    }
+
+    #[test]
+    fn one_shot_clip_finishes_after_its_last_frame_duration() {
+        let clip = clip(PlayMode::OneShot);
+        let mut playback = Playback::default();
+
+        assert_eq!(playback.update("clip", &clip, 2.0), vec![frame_changed(2)]);
+        assert!(!playback.finished);
+        assert_eq!(playback.update("clip", &clip, 0.5), vec![]);
+        assert!(!playback.finished);
+        assert_eq!(playback.update("clip", &clip, 0.5), vec![finished()]);
+        assert!(playback.finished);
+        assert_eq!(playback.frame, 2);
+        assert_eq!(playback.update("clip", &clip, 5.0), vec![]);
+
+        let mut playback = Playback::default();
+        assert_eq!(playback.update("clip", &clip, 10.0), vec![frame_changed(2), finished()]);
+    }
+
+    #[test]
+    fn parse_array_export() {
+        let sheet = SpriteSheet::parse_json(r#"{
+            "frames": [
+                { "filename": "idle_0", "frame": { "x": 0, "y": 0, "w": 16, "h": 16 }, "duration": 200 },
+                { "filename": "idle_1", "frame": { "x": 16, "y": 0, "w": 16, "h": 16 } },
+                { "filename": "run_0", "frame": { "x": 0, "y": 16, "w": 16, "h": 16 } }
+            ],
+            "animations": { "idle": ["idle_0", "idle_1"] }
+        }"#).unwrap();
+
+        assert_eq!(sheet.frames().len(), 3);
+        assert_eq!(sheet.frames()[0].duration, 0.2);
+        assert_eq!(sheet.frames()[1].duration, DEFAULT_FRAME_DURATION);
+        assert_eq!(sheet.frame_index("run_0"), Some(2));
+
+        let idle = sheet.clip("idle").unwrap();
+        assert_eq!(idle.mode, PlayMode::Loop);
+        assert_eq!(idle.frames, sheet.frames()[0..2]);
+    }
+
+    #[test]
+    fn parse_hash_export_keeps_the_frames_order() {
+        let frames = (8..=10)
+            .map(|i| format!(r#""walk {}.ase": {{ "frame": {{ "x": {}, "y": 0, "w": 16, "h": 16 }}, "duration": 100 }}"#, i, (i - 8) * 16))
+            .collect::<Vec<_>>()
+            .join(",");
+        let json = format!(r#"{{
+            "frames": {{ {} }},
+            "meta": {{ "frameTags": [{{ "name": "walk", "from": 1, "to": 2, "direction": "pingpong" }}] }}
+        }}"#, frames);
+        let sheet = SpriteSheet::parse_json(&json).unwrap();
+
+        let x = sheet.frames().iter().map(|frame| frame.rect.left).collect::<Vec<_>>();
+        assert_eq!(x, [0, 16, 32]);
+        assert_eq!(sheet.frame_index("walk 10.ase"), Some(2));
+
+        let walk = sheet.clip("walk").unwrap();
+        assert_eq!(walk.mode, PlayMode::PingPong);
+        assert_eq!(walk.frames.iter().map(|frame| frame.rect.left).collect::<Vec<_>>(), [16, 32]);
+    }
+}
@@ -0,0 +1,349 @@
+use crate::sfml_export::*;
+use crate::core::file_system::FileSystem;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AtlasRegion {
+    pub page: usize,
+    pub rect: IntRect
+}
+
+//////////////// Packing ////////////////////////
+
+struct PackResult {
+    pages_size: Vec<Vector2u>,
+    positions: Vec<(usize, Vector2u)>
+}
+
+/// Shelf packing, the rects are sorted by height and placed row by row
+fn pack_rects(sizes: &[Vector2u], padding: u32, max_page_size: u32, power_of_two: bool) -> Result<PackResult, String> {
+    // The pages are rounded up once packed, they must not go over the maximum size
+    let max_page_size = match power_of_two {
+        true if max_page_size > 0 => 1 << (u32::BITS - 1 - max_page_size.leading_zeros()),
+        _ => max_page_size
+    };
+
+    let mut order = (0..sizes.len()).collect::<Vec<_>>();
+    order.sort_by(|a, b| sizes[*b].y.cmp(&sizes[*a].y).then(sizes[*b].x.cmp(&sizes[*a].x)));
+
+    let mut pages_size = Vec::new();
+    let mut positions = vec![(0, Vector2u::default()); sizes.len()];
+
+    let mut cursor = Vector2u::new(padding, padding);
+    let mut shelf_height = 0;
+    let mut page_size = Vector2u::default();
+
+    for i in order {
+        let size = sizes[i];
+        if size.x + 2 * padding > max_page_size || size.y + 2 * padding > max_page_size {
+            return Err(format!("Image of size {}x{} does not fit in a {} atlas page", size.x, size.y, max_page_size));
+        }
+
+        if cursor.x + size.x + padding > max_page_size {
+            cursor.x = padding;
+            cursor.y += shelf_height + padding;
+            shelf_height = 0;
+        }
+
+        if cursor.y + size.y + padding > max_page_size {
+            pages_size.push(page_size);
+            page_size = Vector2u::default();
+            cursor = Vector2u::new(padding, padding);
+            shelf_height = 0;
+        }
+
+        positions[i] = (pages_size.len(), cursor);
+
+        page_size.x = page_size.x.max(cursor.x + size.x + padding);
+        page_size.y = page_size.y.max(cursor.y + size.y + padding);
+        shelf_height = shelf_height.max(size.y);
+        cursor.x += size.x + padding;
+    }
+
+    if !sizes.is_empty() {
+        pages_size.push(page_size);
+    }
+
+    if power_of_two {
+        for size in pages_size.iter_mut() {
+            size.x = size.x.next_power_of_two();
+            size.y = size.y.next_power_of_two();
+        }
+    }
+
+    Ok(PackResult {
+        pages_size,
+        positions
+    })
+}
+
+//////////////// Builder ////////////////////////
+
+pub struct AtlasBuilder {
+    images: Vec<(String, Image)>,
+    padding: u32,
+    max_page_size: u32,
+    power_of_two: bool
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        Self {
+            images: Vec::new(),
+            padding: 1,
+            max_page_size: 2048,
+            power_of_two: false
+        }
+    }
+
+    /// Empty pixels kept around each image to avoid bleeding when sampling
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn with_max_page_size(mut self, max_page_size: u32) -> Self {
+        self.max_page_size = max_page_size;
+        self
+    }
+
+    pub fn with_power_of_two(mut self, power_of_two: bool) -> Self {
+        self.power_of_two = power_of_two;
+        self
+    }
+
+    pub fn add_image(&mut self, name: &str, image: Image) {
+        assert!(!self.images.iter().any(|(n, _)| n == name), "Atlas region {} already exists", name);
+        self.images.push((name.to_string(), image));
+    }
+
+    /// Packs the images into pages without creating any texture,
+    /// the result can be saved to be loaded later as an atlas asset
+    pub fn pack(&self) -> Result<PackedAtlas, String> {
+        let sizes = self.images.iter().map(|(_, image)| image.size()).collect::<Vec<_>>();
+        let packing = pack_rects(&sizes, self.padding, self.max_page_size, self.power_of_two)?;
+
+        let mut pages = packing.pages_size.iter()
+            .map(|size| Image::from_color(size.x, size.y, Color::TRANSPARENT).ok_or("Cannot create atlas page".to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut regions = HashMap::new();
+        for ((name, image), (page, position)) in self.images.iter().zip(packing.positions) {
+            pages[page].copy_image(image, position.x, position.y, IntRect::default(), false);
+            let size = image.size();
+            regions.insert(name.clone(), AtlasRegion {
+                page,
+                rect: IntRect::new(position.x as i32, position.y as i32, size.x as i32, size.y as i32)
+            });
+        }
+
+        Ok(PackedAtlas {
+            pages,
+            regions
+        })
+    }
+
+    pub fn build(&self) -> Result<TextureAtlas, String> {
+        TextureAtlas::from_packed(&self.pack()?)
+    }
+}
+
+impl Default for AtlasBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//////////////// Packed atlas ////////////////////////
+
+pub struct PackedAtlas {
+    pub pages: Vec<Image>,
+    pub regions: HashMap<String, AtlasRegion>
+}
+
+impl PackedAtlas {
+    /// Saves the pages as `<path>_<page>.png` and the regions as `<path>.atlas`
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let file_name = std::path::Path::new(path).file_name()
+            .ok_or(format!("Invalid atlas path {}", path))?
+            .to_string_lossy();
+
+        let mut description = String::new();
+        for (i, page) in self.pages.iter().enumerate() {
+            let page_path = format!("{}_{}.png", path, i);
+            if !page.save_to_file(&page_path) {
+                return Err(format!("Cannot save atlas page {}", page_path));
+            }
+            writeln!(description, "page {}_{}.png", file_name, i).unwrap();
+        }
+
+        let mut names = self.regions.keys().collect::<Vec<_>>();
+        names.sort();
+        for name in names {
+            let region = self.regions[name];
+            writeln!(description, "region {} {} {} {} {} {}", name, region.page,
+                region.rect.left, region.rect.top, region.rect.width, region.rect.height).unwrap();
+        }
+
+        std::fs::write(format!("{}.atlas", path), description).map_err(|e| e.to_string())
+    }
+}
+
+struct AtlasDescription {
+    pages: Vec<String>,
+    regions: HashMap<String, AtlasRegion>,
+    /// Names and paths of the images to pack when loading
+    images: Vec<(String, String)>
+}
+
+fn parse_atlas_description(description: &str) -> Result<AtlasDescription, String> {
+    let mut pages = Vec::new();
+    let mut regions = HashMap::new();
+    let mut images = Vec::new();
+
+    for (line_number, line) in description.lines().enumerate() {
+        let error = || format!("Invalid atlas description at line {}", line_number + 1);
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            [] => {},
+            ["page", path] => pages.push(path.to_string()),
+            ["image", name, path] => {
+                if images.iter().any(|(image_name, _)| image_name == name) {
+                    return Err(format!("Atlas image {} is listed twice", name));
+                }
+                images.push((name.to_string(), path.to_string()));
+            }
+            ["region", name, values @ ..] if values.len() == 5 => {
+                let values = values.iter()
+                    .map(|v| v.parse::<i32>().map_err(|_| error()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                regions.insert(name.to_string(), AtlasRegion {
+                    page: values[0] as usize,
+                    rect: IntRect::new(values[1], values[2], values[3], values[4])
+                });
+            }
+            _ => return Err(error())
+        }
+    }
+
+    if !images.is_empty() && (!pages.is_empty() || !regions.is_empty()) {
+        return Err("Atlas description mixes packed pages and images to pack".to_string());
+    }
+
+    Ok(AtlasDescription {
+        pages,
+        regions,
+        images
+    })
+}
+
+//////////////// Texture atlas ////////////////////////
+
+pub struct TextureAtlas {
+    pages: Vec<RcTexture>,
+    regions: HashMap<String, AtlasRegion>
+}
+
+impl TextureAtlas {
+    pub fn from_packed(packed: &PackedAtlas) -> Result<Self, String> {
+        let pages = packed.pages.iter().map(|image| {
+            let mut texture = RcTexture::new().ok_or("Cannot create atlas texture".to_string())?;
+            texture.load_from_image(image, IntRect::default()).map_err(|_| "Cannot upload atlas page".to_string())?;
+            Ok(texture)
+        }).collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Self {
+            pages,
+            regions: packed.regions.clone()
+        })
+    }
+
+    /// Loads an atlas saved with `PackedAtlas::save`, or packs the images
+    /// listed as `image <name> <path>` lines of the description.
+    /// The paths are relative to the description file
+    pub fn load(file_system: &FileSystem, path: &str) -> Result<Self, String> {
+        let description = parse_atlas_description(&file_system.read_to_string(path)?)?;
+        let directory = match path.rfind('/') {
+            Some(index) => &path[..=index],
+            None => ""
+        };
+
+        if !description.images.is_empty() {
+            let mut builder = AtlasBuilder::new();
+            for (name, image_path) in description.images.iter() {
+                let data = file_system.read(&format!("{}{}", directory, image_path))?;
+                let image = Image::from_memory(&data).ok_or(format!("Invalid atlas image {}", image_path))?;
+                builder.add_image(name, image);
+            }
+            return builder.build();
+        }
+
+        let pages = description.pages.iter().map(|page| {
+            let data = file_system.read(&format!("{}{}", directory, page))?;
+            let mut texture = RcTexture::new().ok_or("Cannot create atlas texture".to_string())?;
+            texture.load_from_memory(&data, IntRect::default()).map_err(|_| format!("Cannot load atlas page {}", page))?;
+            Ok(texture)
+        }).collect::<Result<Vec<_>, String>>()?;
+
+        if let Some(region) = description.regions.values().find(|r| r.page >= pages.len()) {
+            return Err(format!("Atlas region refers to missing page {}", region.page));
+        }
+
+        Ok(Self {
+            pages,
+            regions: description.regions
+        })
+    }
+
+    pub fn region(&self, name: &str) -> Option<AtlasRegion> {
+        self.regions.get(name).copied()
+    }
+
+    pub fn regions(&self) -> &HashMap<String, AtlasRegion> {
+        &self.regions
+    }
+
+    pub fn page(&self, index: usize) -> &RcTexture {
+        &self.pages[index]
+    }
+
+    pub fn pages_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn texture_of(&self, name: &str) -> Option<&RcTexture> {
+        Some(&self.pages[self.regions.get(name)?.page])
+    }
+
+    /// Sprite displaying the region, it can be used as any other sprite
+    pub fn sprite(&self, name: &str) -> Option<RcSprite> {
+        let region = self.regions.get(name)?;
+        Some(RcSprite::with_texture_and_rect(&self.pages[region.page], region.rect))
+    }
+
+    pub fn set_smooth(&mut self, smooth: bool) {
+        for page in self.pages.iter_mut() {
+            page.set_smooth(smooth);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_of_two_pages_stay_under_the_max_size() {
+        let sizes = vec![Vector2u::new(300, 300); 12];
+        let packing = pack_rects(&sizes, 1, 1000, true).unwrap();
+        assert!(packing.pages_size.len() > 1);
+        for size in packing.pages_size {
+            assert!(size.x <= 512 && size.y <= 512, "page of {}x{}", size.x, size.y);
+            assert!(size.x.is_power_of_two() && size.y.is_power_of_two());
+        }
+
+        let packing = pack_rects(&sizes, 1, 1000, false).unwrap();
+        assert!(packing.pages_size.iter().all(|size| size.x <= 1000 && size.y <= 1000));
+    }
+}
@@ -0,0 +1,330 @@
+use crate::sfml_export::*;
+
+/// How the virtual resolution of a camera is fitted in the window
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScalingMode {
+    /// The virtual area fills the window, the aspect ratio is not kept
+    Stretch,
+    /// The virtual area is scaled to fit in the window, bars fill the remaining space
+    Letterbox,
+    /// The virtual area is scaled to fit in the window, more of the world is shown in the remaining space
+    Expand
+}
+
+/// Trauma based screen shake, the shake grows with the square of the trauma
+#[derive(Clone, Copy, Debug)]
+struct Shake {
+    trauma: f32,
+    /// Trauma removed per second
+    decay: f32,
+    max_offset: Vector2f,
+    /// Degrees
+    max_angle: f32,
+    frequency: f32,
+    time: f32
+}
+
+/// Smooth noise in [-1, 1], a different seed gives an unrelated curve
+fn shake_noise(time: f32, seed: f32) -> f32 {
+    ((time + seed).sin() * 0.5 + (time * 2.3 + seed * 1.7).sin() * 0.3 + (time * 4.1 + seed * 3.1).sin() * 0.2).clamp(-1.0, 1.0)
+}
+
+fn rotate(v: Vector2f, degrees: f32) -> Vector2f {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    Vector2f::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
+
+/// View on the world with a virtual resolution
+///
+/// The camera shows `virtual_size` world units at zoom 1, fitted in the window with its `ScalingMode`.
+/// `update` moves it towards the followed target and animates the shake,
+/// `apply` sets its view on the render target.
+pub struct Camera2D {
+    virtual_size: Vector2f,
+    scaling_mode: ScalingMode,
+    center: Vector2f,
+    zoom: f32,
+    rotation: f32,
+    target: Option<Vector2f>,
+    follow_speed: f32,
+    bounds: Option<FloatRect>,
+    shake: Shake,
+    window_size: Vector2f,
+    view: SfBox<View>
+}
+
+impl Camera2D {
+    pub fn new(virtual_size: impl Into<Vector2f>) -> Self {
+        let virtual_size = virtual_size.into();
+        let center = virtual_size / 2.0;
+        Self {
+            virtual_size,
+            scaling_mode: ScalingMode::Letterbox,
+            center,
+            zoom: 1.0,
+            rotation: 0.0,
+            target: None,
+            follow_speed: 5.0,
+            bounds: None,
+            shake: Shake {
+                trauma: 0.0,
+                decay: 1.0,
+                max_offset: Vector2f::new(20.0, 20.0),
+                max_angle: 5.0,
+                frequency: 25.0,
+                time: 0.0
+            },
+            window_size: virtual_size,
+            view: View::new(center, virtual_size)
+        }
+    }
+
+    pub fn with_scaling_mode(mut self, scaling_mode: ScalingMode) -> Self {
+        self.scaling_mode = scaling_mode;
+        self
+    }
+
+    /// How fast the camera reaches the followed target, 0 reaches it instantly
+    pub fn with_follow_speed(mut self, follow_speed: f32) -> Self {
+        self.follow_speed = follow_speed;
+        self
+    }
+
+    /// Shake at full trauma, the angle is in degrees and the frequency in noise cycles per second
+    pub fn with_shake(mut self, max_offset: impl Into<Vector2f>, max_angle: f32, frequency: f32) -> Self {
+        self.shake.max_offset = max_offset.into();
+        self.shake.max_angle = max_angle;
+        self.shake.frequency = frequency;
+        self
+    }
+
+    /// Trauma removed per second
+    pub fn with_trauma_decay(mut self, decay: f32) -> Self {
+        self.shake.decay = decay;
+        self
+    }
+
+    pub fn with_bounds(mut self, bounds: FloatRect) -> Self {
+        self.set_bounds(Some(bounds));
+        self
+    }
+
+    //////////////// Position ////////////////////////
+
+    pub fn center(&self) -> Vector2f {
+        self.center
+    }
+
+    /// Moves the camera immediately, the followed target is kept
+    pub fn set_center(&mut self, center: impl Into<Vector2f>) {
+        self.center = center.into();
+        self.clamp_center();
+    }
+
+    pub fn move_by(&mut self, offset: impl Into<Vector2f>) {
+        self.set_center(self.center + offset.into());
+    }
+
+    /// The camera moves towards the target on each update
+    pub fn follow(&mut self, target: impl Into<Vector2f>) {
+        self.target = Some(target.into());
+    }
+
+    pub fn stop_following(&mut self) {
+        self.target = None;
+    }
+
+    /// The camera never shows outside of the bounds, it is centered on them when they are smaller than the view
+    pub fn set_bounds(&mut self, bounds: Option<FloatRect>) {
+        self.bounds = bounds;
+        self.clamp_center();
+    }
+
+    pub fn bounds(&self) -> Option<FloatRect> {
+        self.bounds
+    }
+
+    //////////////// Zoom and rotation ////////////////////////
+
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    /// Greater than 1 zooms in
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom.max(f32::EPSILON);
+        self.clamp_center();
+    }
+
+    /// Zooms by the factor while keeping the world point under the window pixel in place
+    pub fn zoom_at(&mut self, factor: f32, pixel: Vector2i) {
+        let before = self.screen_to_world(pixel);
+        self.zoom = (self.zoom * factor).max(f32::EPSILON);
+        let after = self.screen_to_world(pixel);
+        self.set_center(self.center + before - after);
+    }
+
+    /// Degrees
+    pub fn rotation(&self) -> f32 {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.rotation = rotation % 360.0;
+        self.clamp_center();
+    }
+
+    //////////////// Shake ////////////////////////
+
+    /// Adds trauma, clamped between 0 and 1
+    pub fn add_trauma(&mut self, trauma: f32) {
+        self.shake.trauma = (self.shake.trauma + trauma).clamp(0.0, 1.0);
+    }
+
+    pub fn trauma(&self) -> f32 {
+        self.shake.trauma
+    }
+
+    fn shake_offset(&self) -> (Vector2f, f32) {
+        let amount = self.shake.trauma * self.shake.trauma;
+        if amount == 0.0 {
+            return (Vector2f::default(), 0.0);
+        }
+
+        let time = self.shake.time * self.shake.frequency;
+        let offset = Vector2f::new(
+            self.shake.max_offset.x * amount * shake_noise(time, 0.0),
+            self.shake.max_offset.y * amount * shake_noise(time, 10.0)
+        );
+        (offset / self.zoom, self.shake.max_angle * amount * shake_noise(time, 20.0))
+    }
+
+    //////////////////////////////////////
+
+    /// Follows the target and animates the shake, usually called with `StateData::delta_time`
+    pub fn update(&mut self, delta_time: f32) {
+        if let Some(target) = self.target {
+            // Exponential smoothing, independent from the frame rate
+            let t = if self.follow_speed > 0.0 { 1.0 - (-self.follow_speed * delta_time).exp() } else { 1.0 };
+            self.center += (target - self.center) * t;
+            self.clamp_center();
+        }
+
+        self.shake.time += delta_time;
+        self.shake.trauma = (self.shake.trauma - self.shake.decay * delta_time).max(0.0);
+    }
+
+    /// Sets the camera view on the target, the viewport is fitted in its current size
+    pub fn apply(&mut self, target: &mut dyn RenderTarget) {
+        self.window_size = target.size().as_other();
+        self.update_view();
+        target.set_view(&self.view);
+    }
+
+    /// View of the last `apply`
+    pub fn view(&self) -> &View {
+        &self.view
+    }
+
+    /// World position displayed at the window pixel, for mouse picking
+    pub fn screen_to_world(&self, pixel: Vector2i) -> Vector2f {
+        let (center, size, rotation, viewport) = self.view_geometry();
+        let viewport_pos = Vector2f::new(viewport.left * self.window_size.x, viewport.top * self.window_size.y);
+        let viewport_size = Vector2f::new(viewport.width * self.window_size.x, viewport.height * self.window_size.y);
+
+        let pixel = pixel.as_other::<f32>();
+        let local = Vector2f::new(
+            ((pixel.x - viewport_pos.x) / viewport_size.x - 0.5) * size.x,
+            ((pixel.y - viewport_pos.y) / viewport_size.y - 0.5) * size.y
+        );
+        center + rotate(local, rotation)
+    }
+
+    /// Window pixel where the world position is displayed
+    pub fn world_to_screen(&self, point: Vector2f) -> Vector2i {
+        let (center, size, rotation, viewport) = self.view_geometry();
+        let viewport_pos = Vector2f::new(viewport.left * self.window_size.x, viewport.top * self.window_size.y);
+        let viewport_size = Vector2f::new(viewport.width * self.window_size.x, viewport.height * self.window_size.y);
+
+        let local = rotate(point - center, -rotation);
+        Vector2f::new(
+            (local.x / size.x + 0.5) * viewport_size.x + viewport_pos.x,
+            (local.y / size.y + 0.5) * viewport_size.y + viewport_pos.y
+        ).as_other()
+    }
+
+    /// World area visible without the shake, the bounding box when the camera is rotated
+    pub fn visible_area(&self) -> FloatRect {
+        let size = self.view_size();
+        let half = self.rotated_half_extents(size);
+        FloatRect::new(self.center.x - half.x, self.center.y - half.y, half.x * 2.0, half.y * 2.0)
+    }
+
+    //////////////// Geometry ////////////////////////
+
+    /// Scale from the virtual resolution to the window pixels
+    fn fit_scale(&self) -> f32 {
+        (self.window_size.x / self.virtual_size.x).min(self.window_size.y / self.virtual_size.y)
+    }
+
+    fn view_size(&self) -> Vector2f {
+        let size = match self.scaling_mode {
+            ScalingMode::Stretch | ScalingMode::Letterbox => self.virtual_size,
+            ScalingMode::Expand => self.window_size / self.fit_scale()
+        };
+        size / self.zoom
+    }
+
+    fn viewport(&self) -> FloatRect {
+        match self.scaling_mode {
+            ScalingMode::Stretch | ScalingMode::Expand => FloatRect::new(0.0, 0.0, 1.0, 1.0),
+            ScalingMode::Letterbox => {
+                let scale = self.fit_scale();
+                let width = self.virtual_size.x * scale / self.window_size.x;
+                let height = self.virtual_size.y * scale / self.window_size.y;
+                FloatRect::new((1.0 - width) / 2.0, (1.0 - height) / 2.0, width, height)
+            }
+        }
+    }
+
+    /// Center, size, rotation and viewport of the view, with the shake
+    fn view_geometry(&self) -> (Vector2f, Vector2f, f32, FloatRect) {
+        let (shake_offset, shake_angle) = self.shake_offset();
+        (self.center + shake_offset, self.view_size(), self.rotation + shake_angle, self.viewport())
+    }
+
+    fn update_view(&mut self) {
+        let (center, size, rotation, viewport) = self.view_geometry();
+        self.view.set_center(center);
+        self.view.set_size(size);
+        self.view.set_rotation(rotation);
+        self.view.set_viewport(viewport);
+    }
+
+    fn rotated_half_extents(&self, size: Vector2f) -> Vector2f {
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        Vector2f::new(
+            (size.x * cos.abs() + size.y * sin.abs()) / 2.0,
+            (size.x * sin.abs() + size.y * cos.abs()) / 2.0
+        )
+    }
+
+    fn clamp_center(&mut self) {
+        let bounds = match self.bounds {
+            Some(bounds) => bounds,
+            None => return
+        };
+
+        let half = self.rotated_half_extents(self.view_size());
+        let clamp_axis = |center: f32, start: f32, length: f32, half: f32| {
+            if length <= half * 2.0 {
+                start + length / 2.0
+            }
+            else {
+                center.clamp(start + half, start + length - half)
+            }
+        };
+        self.center.x = clamp_axis(self.center.x, bounds.left, bounds.width, half.x);
+        self.center.y = clamp_axis(self.center.y, bounds.top, bounds.height, half.y);
+    }
+}
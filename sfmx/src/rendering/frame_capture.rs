@@ -0,0 +1,424 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use crate::sfml_export::*;
+
+/// Copies the current content of the window, to call before `display`
+pub fn capture_window(window: &RenderWindow) -> Option<Image> {
+    let size = window.size();
+    let mut texture = Texture::new()?;
+    if !texture.create(size.x, size.y) {
+        return None;
+    }
+    // The texture has the size of the window
+    unsafe {
+        texture.update_from_render_window(window, 0, 0);
+    }
+    texture.copy_to_image()
+}
+
+/// Offscreen counterpart of `capture_window`
+pub fn capture_render_texture(render_texture: &RenderTexture) -> Option<Image> {
+    render_texture.texture().copy_to_image()
+}
+
+//////////////// GIF encoding ////////////////////////
+
+/// Uniform palette of 6 red, 7 green and 6 blue levels
+fn palette_index(r: u8, g: u8, b: u8) -> u8 {
+    let r = r as u16 * 6 / 256;
+    let g = g as u16 * 7 / 256;
+    let b = b as u16 * 6 / 256;
+    (r * 42 + g * 6 + b) as u8
+}
+
+fn palette() -> Vec<u8> {
+    let mut palette = vec![0; 256 * 3];
+    for r in 0..6 {
+        for g in 0..7 {
+            for b in 0..6 {
+                let index = (r * 42 + g * 6 + b) * 3;
+                palette[index] = (r * 255 / 5) as u8;
+                palette[index + 1] = (g * 255 / 6) as u8;
+                palette[index + 2] = (b * 255 / 5) as u8;
+            }
+        }
+    }
+    palette
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u32
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// The code size grows when the next entry of the decoder does not fit anymore
+fn emit(writer: &mut BitWriter, code: u16, next_code: u16, code_size: &mut u32) {
+    writer.write(code, *code_size);
+    if next_code >= 1 << *code_size && *code_size < 12 {
+        *code_size += 1;
+    }
+}
+
+/// Variable length LZW with 8 bits indices, codes up to 12 bits
+fn lzw_encode(indices: &[u8]) -> Vec<u8> {
+    const CLEAR_CODE: u16 = 256;
+    const END_CODE: u16 = 257;
+    const FIRST_CODE: u16 = 258;
+
+    let mut writer = BitWriter { bytes: Vec::new(), buffer: 0, bits: 0 };
+    let mut dictionary = HashMap::<(u16, u8), u16>::new();
+    let mut next_code = FIRST_CODE;
+    let mut code_size = 9;
+
+    writer.write(CLEAR_CODE, code_size);
+    let mut current = None;
+    for &index in indices {
+        let prefix = match current {
+            Some(prefix) => prefix,
+            None => {
+                current = Some(index as u16);
+                continue;
+            }
+        };
+
+        if let Some(&code) = dictionary.get(&(prefix, index)) {
+            current = Some(code);
+            continue;
+        }
+
+        emit(&mut writer, prefix, next_code, &mut code_size);
+        if next_code < 4096 {
+            dictionary.insert((prefix, index), next_code);
+            next_code += 1;
+        }
+        else {
+            writer.write(CLEAR_CODE, code_size);
+            dictionary.clear();
+            next_code = FIRST_CODE;
+            code_size = 9;
+        }
+        current = Some(index as u16);
+    }
+
+    if let Some(prefix) = current {
+        emit(&mut writer, prefix, next_code, &mut code_size);
+    }
+    writer.write(END_CODE, code_size);
+    writer.finish()
+}
+
+/// Encodes the frames in an animated GIF looping forever,
+/// the colors are reduced to a uniform 252 colors palette
+pub fn encode_gif(frames: &[Image], frame_delay: std::time::Duration) -> Result<Vec<u8>, String> {
+    let size = frames.first().ok_or("No frame to encode".to_string())?.size();
+    if frames.iter().any(|frame| frame.size() != size) {
+        return Err("The frames have different sizes".to_string());
+    }
+    if size.x > u16::MAX as u32 || size.y > u16::MAX as u32 {
+        return Err(format!("Frames of {}x{} are too large for a GIF", size.x, size.y));
+    }
+
+    let width = (size.x as u16).to_le_bytes();
+    let height = (size.y as u16).to_le_bytes();
+    let delay = ((frame_delay.as_millis() / 10).min(u16::MAX as u128) as u16).to_le_bytes();
+
+    let mut gif = Vec::new();
+    gif.extend_from_slice(b"GIF89a");
+    gif.extend_from_slice(&width);
+    gif.extend_from_slice(&height);
+    // Global color table of 256 entries
+    gif.extend_from_slice(&[0xF7, 0, 0]);
+    gif.extend_from_slice(&palette());
+    // Loops forever
+    gif.extend_from_slice(&[0x21, 0xFF, 0x0B]);
+    gif.extend_from_slice(b"NETSCAPE2.0");
+    gif.extend_from_slice(&[0x03, 0x01, 0x00, 0x00, 0x00]);
+
+    for frame in frames {
+        gif.extend_from_slice(&[0x21, 0xF9, 0x04, 0x04, delay[0], delay[1], 0x00, 0x00]);
+        gif.push(0x2C);
+        gif.extend_from_slice(&[0, 0, 0, 0]);
+        gif.extend_from_slice(&width);
+        gif.extend_from_slice(&height);
+        gif.push(0x00);
+
+        let indices = frame.pixel_data()
+            .chunks_exact(4)
+            .map(|pixel| palette_index(pixel[0], pixel[1], pixel[2]))
+            .collect::<Vec<_>>();
+
+        gif.push(8);
+        for block in lzw_encode(&indices).chunks(255) {
+            gif.push(block.len() as u8);
+            gif.extend_from_slice(block);
+        }
+        gif.push(0x00);
+    }
+
+    gif.push(0x3B);
+    Ok(gif)
+}
+
+//////////////// Frame capture ////////////////////////
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecordFormat {
+    /// `<path>_0000.png`, `<path>_0001.png`...
+    NumberedPng,
+    /// Single `<path>.gif` written when the recording stops
+    Gif
+}
+
+struct Recording {
+    path: String,
+    format: RecordFormat,
+    frame_interval: f32,
+    elapsed: f32,
+    frames: Vec<Image>,
+    frames_count: usize
+}
+
+/// Screenshots and recordings of the rendered frames
+///
+/// The application captures its window after the states rendering,
+/// a headless application rendering to a `RenderTexture` calls `on_frame` itself.
+pub struct FrameCapture {
+    screenshot_requests: Vec<String>,
+    screenshot_key: Option<Key>,
+    screenshots_directory: PathBuf,
+    screenshots_count: usize,
+    recording: Option<Recording>,
+    errors: Vec<String>
+}
+
+impl FrameCapture {
+    pub fn new() -> Self {
+        Self {
+            screenshot_requests: Vec::new(),
+            screenshot_key: None,
+            screenshots_directory: PathBuf::from("."),
+            screenshots_count: 0,
+            recording: None,
+            errors: Vec::new()
+        }
+    }
+
+    /// Saves the next rendered frame
+    pub fn request_screenshot(&mut self, path: &str) {
+        self.screenshot_requests.push(path.to_string());
+    }
+
+    /// Key taking a screenshot in the screenshots directory
+    pub fn set_screenshot_key(&mut self, key: Option<Key>) {
+        self.screenshot_key = key;
+    }
+
+    pub fn set_screenshots_directory(&mut self, directory: impl Into<PathBuf>) {
+        self.screenshots_directory = directory.into();
+    }
+
+    /// Records the frames at most `fps` times per second until `stop_recording`
+    pub fn start_recording(&mut self, path: &str, format: RecordFormat, fps: f32) {
+        self.recording = Some(Recording {
+            path: path.to_string(),
+            format,
+            frame_interval: 1.0 / fps,
+            elapsed: 0.0,
+            frames: Vec::new(),
+            frames_count: 0
+        });
+    }
+
+    /// Writes the GIF of the recording, returns the number of recorded frames
+    pub fn stop_recording(&mut self) -> Result<usize, String> {
+        let recording = self.recording.take().ok_or("No recording in progress".to_string())?;
+        if recording.format == RecordFormat::Gif {
+            let delay = std::time::Duration::from_secs_f32(recording.frame_interval);
+            let gif = encode_gif(&recording.frames, delay)?;
+            let path = format!("{}.gif", recording.path);
+            std::fs::write(&path, gif).map_err(|e| format!("Cannot write {}: {}", path, e))?;
+        }
+        Ok(recording.frames_count)
+    }
+
+    /// Failures of the captures made by the application, which cannot return them
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    pub(crate) fn add_error(&mut self, error: String) {
+        // A capture failing on every frame is kept once
+        if self.errors.last() != Some(&error) {
+            self.errors.push(error);
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// True if the next frame has to be captured
+    pub fn wants_frame(&self, delta_time: f32) -> bool {
+        !self.screenshot_requests.is_empty() || self.recording.as_ref()
+            .map(|recording| recording.frames_count == 0 || recording.elapsed + delta_time >= recording.frame_interval)
+            .unwrap_or(false)
+    }
+
+    pub fn on_event(&mut self, event: Event) {
+        if let (Event::KeyPressed { code, .. }, Some(key)) = (event, self.screenshot_key) {
+            if code == key {
+                let path = self.screenshots_directory.join(format!("screenshot_{:04}.png", self.screenshots_count));
+                self.screenshots_count += 1;
+                self.request_screenshot(&path.to_string_lossy());
+            }
+        }
+    }
+
+    /// Called once per rendered frame, `grab` is only called when the frame is needed
+    pub fn on_frame(&mut self, delta_time: f32, grab: impl FnOnce() -> Option<Image>) -> Result<(), String> {
+        if !self.wants_frame(delta_time) {
+            if let Some(recording) = &mut self.recording {
+                recording.elapsed += delta_time;
+            }
+            return Ok(());
+        }
+
+        let frame = grab().ok_or("Cannot capture the frame".to_string())?;
+        for path in self.screenshot_requests.drain(..) {
+            if !frame.save_to_file(&path) {
+                return Err(format!("Cannot save screenshot {}", path));
+            }
+        }
+
+        if let Some(recording) = &mut self.recording {
+            if recording.frames_count == 0 || recording.elapsed + delta_time >= recording.frame_interval {
+                recording.elapsed = 0.0;
+                match recording.format {
+                    RecordFormat::NumberedPng => {
+                        let path = format!("{}_{:04}.png", recording.path, recording.frames_count);
+                        if !frame.save_to_file(&path) {
+                            return Err(format!("Cannot save frame {}", path));
+                        }
+                    },
+                    RecordFormat::Gif => recording.frames.push(frame)
+                }
+                recording.frames_count += 1;
+            }
+            else {
+                recording.elapsed += delta_time;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for FrameCapture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decoder following the GIF specification, the code size grows once the
+    /// dictionary fills the current size and stays at 12 bits until a clear code
+    fn lzw_decode(bytes: &[u8]) -> Vec<u8> {
+        let mut dictionary = (0..=255u8).map(|i| vec![i]).chain([Vec::new(), Vec::new()]).collect::<Vec<_>>();
+        let mut code_size = 9;
+        let mut previous: Option<usize> = None;
+        let mut res = Vec::new();
+
+        let (mut buffer, mut bits, mut bytes) = (0u32, 0, bytes.iter());
+        loop {
+            while bits < code_size {
+                buffer |= (*bytes.next().expect("Missing end code") as u32) << bits;
+                bits += 8;
+            }
+            let code = (buffer & ((1 << code_size) - 1)) as usize;
+            buffer >>= code_size;
+            bits -= code_size;
+
+            match code {
+                256 => {
+                    dictionary.truncate(258);
+                    code_size = 9;
+                    previous = None;
+                    continue;
+                },
+                257 => return res,
+                _ => {}
+            }
+
+            let entry = match previous {
+                None => dictionary[code].clone(),
+                Some(previous) => {
+                    let entry = if code < dictionary.len() {
+                        dictionary[code].clone()
+                    }
+                    else {
+                        assert_eq!(code, dictionary.len(), "Code {} is not in the dictionary", code);
+                        let mut entry = dictionary[previous].clone();
+                        entry.push(entry[0]);
+                        entry
+                    };
+                    if dictionary.len() < 4096 {
+                        let mut new_entry = dictionary[previous].clone();
+                        new_entry.push(entry[0]);
+                        dictionary.push(new_entry);
+                        if dictionary.len() == 1 << code_size && code_size < 12 {
+                            code_size += 1;
+                        }
+                    }
+                    entry
+                }
+            };
+            res.extend_from_slice(&entry);
+            previous = Some(code);
+        }
+    }
+
+    #[test]
+    fn lzw_reference_bytes() {
+        // Codes 256 (clear), 1, 258, 1, 257 (end) on 9 bits
+        assert_eq!(lzw_encode(&[1, 1, 1, 1]), vec![0x00, 0x03, 0x08, 0x0C, 0x10, 0x10]);
+        assert_eq!(lzw_decode(&lzw_encode(&[])), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn lzw_round_trip() {
+        // Pseudo random indices fill the dictionary several times, exercising the clear codes
+        let mut seed = 12345u32;
+        let noisy = (0..100_000).map(|_| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (seed >> 16) as u8
+        }).collect::<Vec<_>>();
+        let repetitive = (0..50_000u32).map(|i| (i / 7 % 5) as u8).collect::<Vec<_>>();
+        let low_entropy = noisy.iter().map(|index| index % 3).collect::<Vec<_>>();
+
+        for indices in [noisy, repetitive, low_entropy] {
+            assert_eq!(lzw_decode(&lzw_encode(&indices)), indices);
+        }
+    }
+}
@@ -0,0 +1,543 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use toml::{Table, Value};
+use crate::sfml_export::*;
+use crate::core::file_system::FileSystem;
+use crate::core::tween::Tweenable;
+use super::atlas::TextureAtlas;
+use super::QUAD_INDICES;
+
+//////////////// Config ////////////////////////
+
+const DEFAULT_SIZE: f32 = 4.0;
+const DEFAULT_COLOR: Color = Color::WHITE;
+
+/// Settings of a particle emitter, the ranges are (min, max) picked randomly for each particle
+///
+/// The size and color are keys evenly spread over the lifetime of a particle and
+/// interpolated between them. The angles are in degrees, 0 pointing right and 90 down.
+#[derive(Clone, Debug)]
+pub struct ParticleConfig {
+    pub max_particles: usize,
+    /// Particles per second while emitting
+    pub spawn_rate: f32,
+    /// Particles spawned at once when the emitter starts
+    pub burst: u32,
+    /// Seconds of emission after the start, 0 emits until stopped
+    pub duration: f32,
+    pub lifetime: (f32, f32),
+    pub speed: (f32, f32),
+    pub direction: f32,
+    /// Width of the cone of the initial velocities
+    pub spread: f32,
+    pub gravity: Vector2f,
+    /// Part of the velocity lost per second
+    pub damping: f32,
+    /// Size of the rectangle centered on the emitter where the particles spawn
+    pub spawn_area: Vector2f,
+    pub sizes: Vec<f32>,
+    pub colors: Vec<Color>,
+    pub rotation: (f32, f32),
+    pub angular_velocity: (f32, f32),
+    /// Texture rects picked randomly for each particle, untextured squares when empty
+    pub texture_rects: Vec<IntRect>,
+    /// Atlas regions names, turned into texture rects by `resolve_regions`
+    pub regions: Vec<String>
+}
+
+impl Default for ParticleConfig {
+    fn default() -> Self {
+        Self {
+            max_particles: 1000,
+            spawn_rate: 20.0,
+            burst: 0,
+            duration: 0.0,
+            lifetime: (1.0, 1.0),
+            speed: (50.0, 100.0),
+            direction: -90.0,
+            spread: 30.0,
+            gravity: Vector2f::default(),
+            damping: 0.0,
+            spawn_area: Vector2f::default(),
+            sizes: vec![DEFAULT_SIZE],
+            colors: vec![DEFAULT_COLOR],
+            rotation: (0.0, 0.0),
+            angular_velocity: (0.0, 0.0),
+            texture_rects: Vec::new(),
+            regions: Vec::new()
+        }
+    }
+}
+
+fn parse_number(key: &str, value: &Value) -> Result<f32, String> {
+    match value {
+        Value::Float(v) => Ok(*v as f32),
+        Value::Integer(v) => Ok(*v as f32),
+        _ => Err(format!("{} should be a number", key))
+    }
+}
+
+fn parse_numbers(key: &str, value: &Value) -> Result<Vec<f32>, String> {
+    match value {
+        Value::Array(values) => values.iter().map(|v| parse_number(key, v)).collect(),
+        _ => Ok(vec![parse_number(key, value)?])
+    }
+}
+
+fn parse_count(key: &str, value: &Value) -> Result<u32, String> {
+    value.as_integer().and_then(|v| u32::try_from(v).ok()).ok_or(format!("{} should be a positive integer", key))
+}
+
+/// A number or a [min, max] array
+fn parse_range(key: &str, value: &Value) -> Result<(f32, f32), String> {
+    match parse_numbers(key, value)?.as_slice() {
+        [v] => Ok((*v, *v)),
+        [min, max] => Ok((*min, *max)),
+        _ => Err(format!("{} should be a number or a [min, max] array", key))
+    }
+}
+
+fn parse_vector(key: &str, value: &Value) -> Result<Vector2f, String> {
+    match parse_numbers(key, value)?.as_slice() {
+        [x, y] => Ok(Vector2f::new(*x, *y)),
+        _ => Err(format!("{} should be a [x, y] array", key))
+    }
+}
+
+/// "#rrggbb", "#rrggbbaa" or a [r, g, b] or [r, g, b, a] array
+fn parse_color(key: &str, value: &Value) -> Result<Color, String> {
+    let error = || format!("Invalid color in {}", key);
+    let channels = match value {
+        Value::String(hex) => {
+            let hex = hex.strip_prefix('#').ok_or_else(error)?;
+            if hex.len() != 6 && hex.len() != 8 {
+                return Err(error());
+            }
+            (0..hex.len()).step_by(2)
+                .map(|i| hex.get(i..i + 2).and_then(|channel| u8::from_str_radix(channel, 16).ok()).ok_or_else(error))
+                .collect::<Result<Vec<_>, String>>()?
+        },
+        Value::Array(values) => values.iter()
+            .map(|v| v.as_integer().and_then(|v| u8::try_from(v).ok()).ok_or_else(error))
+            .collect::<Result<Vec<_>, String>>()?,
+        _ => return Err(error())
+    };
+
+    match channels.as_slice() {
+        [r, g, b] => Ok(Color::rgb(*r, *g, *b)),
+        [r, g, b, a] => Ok(Color::rgba(*r, *g, *b, *a)),
+        _ => Err(error())
+    }
+}
+
+impl ParticleConfig {
+    /// Toml file whose keys are the fields names, the missing ones keep their default value
+    ///
+    /// ```toml
+    /// spawn_rate = 0
+    /// burst = 200
+    /// lifetime = [0.8, 1.2]
+    /// gravity = [0, 400]
+    /// sizes = [6, 0]
+    /// colors = ["#ffd700", "#ff450000"]
+    /// ```
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let table = content.parse::<Table>().map_err(|e| format!("Invalid particle config: {}", e))?;
+        let mut res = Self::default();
+
+        for (key, value) in table.iter() {
+            let key = key.as_str();
+            match key {
+                "max_particles" => res.max_particles = parse_count(key, value)? as usize,
+                "spawn_rate" => res.spawn_rate = parse_number(key, value)?.max(0.0),
+                "burst" => res.burst = parse_count(key, value)?,
+                "duration" => res.duration = parse_number(key, value)?.max(0.0),
+                "lifetime" => res.lifetime = parse_range(key, value)?,
+                "speed" => res.speed = parse_range(key, value)?,
+                "direction" => res.direction = parse_number(key, value)?,
+                "spread" => res.spread = parse_number(key, value)?,
+                "gravity" => res.gravity = parse_vector(key, value)?,
+                "damping" => res.damping = parse_number(key, value)?.max(0.0),
+                "spawn_area" => res.spawn_area = parse_vector(key, value)?,
+                "sizes" => res.sizes = parse_numbers(key, value)?,
+                "colors" => res.colors = match value {
+                    Value::Array(colors) if colors.iter().all(|c| !c.is_integer()) => {
+                        colors.iter().map(|c| parse_color(key, c)).collect::<Result<_, _>>()?
+                    },
+                    _ => vec![parse_color(key, value)?]
+                },
+                "rotation" => res.rotation = parse_range(key, value)?,
+                "angular_velocity" => res.angular_velocity = parse_range(key, value)?,
+                "texture_rects" => res.texture_rects = value.as_array().ok_or("texture_rects should be an array")?.iter()
+                    .map(|rect| match parse_numbers(key, rect)?.as_slice() {
+                        [left, top, width, height] => Ok(IntRect::new(*left as i32, *top as i32, *width as i32, *height as i32)),
+                        _ => Err("texture_rects should contain [left, top, width, height] arrays".to_string())
+                    })
+                    .collect::<Result<_, String>>()?,
+                "regions" => res.regions = value.as_array().ok_or("regions should be an array")?.iter()
+                    .map(|name| name.as_str().map(str::to_string).ok_or("regions should contain names".to_string()))
+                    .collect::<Result<_, String>>()?,
+                _ => return Err(format!("Unknown particle setting {}", key))
+            }
+        }
+
+        if res.sizes.is_empty() || res.colors.is_empty() {
+            return Err("The sizes and colors need at least one value".to_string());
+        }
+        Ok(res)
+    }
+
+    pub fn load(file_system: &FileSystem, path: &str) -> Result<Self, String> {
+        Self::parse(&file_system.read_to_string(path)?).map_err(|e| format!("{} in {}", e, path))
+    }
+
+    /// Adds the rects of the named regions to the texture rects, returns the atlas page holding them
+    ///
+    /// `ParticleEmitter::resolve_regions` also sets the page as the texture of the emitter.
+    pub fn resolve_regions(&mut self, atlas: &TextureAtlas) -> Result<Option<usize>, String> {
+        let mut page = None;
+        let mut rects = Vec::new();
+        for name in self.regions.iter() {
+            let region = atlas.region(name).ok_or(format!("Unknown atlas region {}", name))?;
+            if page.is_some_and(|page| page != region.page) {
+                return Err("The particle regions should be in the same atlas page".to_string());
+            }
+            page = Some(region.page);
+            rects.push(region.rect);
+        }
+
+        self.regions.clear();
+        self.texture_rects.extend(rects);
+        Ok(page)
+    }
+}
+
+//////////////// Emitter ////////////////////////
+
+/// Xorshift generator, good enough for effects
+struct Random(u64);
+
+impl Random {
+    fn new() -> Self {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64);
+        Self(seed | 1)
+    }
+
+    /// In [0, 1)
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn range(&mut self, (min, max): (f32, f32)) -> f32 {
+        min + (max - min) * self.next()
+    }
+}
+
+/// Value at `t` in [0, 1] of keys evenly spread over [0, 1], the default without keys
+fn sample<V: Tweenable>(keys: &[V], t: f32, default: V) -> V {
+    if keys.is_empty() {
+        return default;
+    }
+    let position = t.clamp(0.0, 1.0) * (keys.len() - 1) as f32;
+    let index = (position as usize).min(keys.len() - 1);
+    match keys.get(index + 1) {
+        Some(next) => V::interpolate(keys[index], *next, position - index as f32),
+        None => keys[index]
+    }
+}
+
+struct Particle {
+    position: Vector2f,
+    velocity: Vector2f,
+    rotation: f32,
+    angular_velocity: f32,
+    age: f32,
+    lifetime: f32,
+    texture_rect: Option<IntRect>
+}
+
+/// Particles simulated on the CPU and drawn in a single draw call
+///
+/// The particles live in world space, moving the emitter only moves where the new ones spawn.
+/// The texture rects of the config are in the pixels of the emitter texture, or of the
+/// texture of the states given to draw when the emitter has none.
+pub struct ParticleEmitter {
+    config: ParticleConfig,
+    /// Sprite keeping a weak reference to the shared texture of the particles
+    texture: Option<RcSprite>,
+    particles: Vec<Particle>,
+    vertices: Vec<Vertex>,
+    position: Vector2f,
+    emitting: bool,
+    emission_time: f32,
+    spawn_accumulator: f32,
+    random: Random
+}
+
+impl ParticleEmitter {
+    pub fn new(config: ParticleConfig) -> Self {
+        Self {
+            config,
+            texture: None,
+            particles: Vec::new(),
+            vertices: Vec::new(),
+            position: Vector2f::default(),
+            emitting: false,
+            emission_time: 0.0,
+            spawn_accumulator: 0.0,
+            random: Random::new()
+        }
+    }
+
+    pub fn with_position(mut self, position: impl Into<Vector2f>) -> Self {
+        self.position = position.into();
+        self
+    }
+
+    /// Shared texture of the particles, such as an assets manager texture
+    pub fn with_texture(mut self, texture: &RcTexture) -> Self {
+        self.set_texture(texture);
+        self
+    }
+
+    pub fn set_texture(&mut self, texture: &RcTexture) {
+        self.texture = Some(RcSprite::with_texture(texture));
+    }
+
+    /// The texture of the states given to draw is used
+    pub fn remove_texture(&mut self) {
+        self.texture = None;
+    }
+
+    /// Resolves the regions of the config and draws the particles with the atlas page holding them
+    pub fn resolve_regions(&mut self, atlas: &TextureAtlas) -> Result<(), String> {
+        if let Some(page) = self.config.resolve_regions(atlas)? {
+            self.set_texture(atlas.page(page));
+        }
+        Ok(())
+    }
+
+    pub fn config(&self) -> &ParticleConfig {
+        &self.config
+    }
+
+    pub fn config_mut(&mut self) -> &mut ParticleConfig {
+        &mut self.config
+    }
+
+    pub fn position(&self) -> Vector2f {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: impl Into<Vector2f>) {
+        self.position = position.into();
+    }
+
+    /// Spawns the burst then emits at the spawn rate
+    pub fn start(&mut self) {
+        self.emitting = true;
+        self.emission_time = 0.0;
+        self.spawn_accumulator = 0.0;
+        self.emit(self.config.burst);
+    }
+
+    /// No new particles, the living ones finish their life
+    pub fn stop(&mut self) {
+        self.emitting = false;
+    }
+
+    pub fn is_emitting(&self) -> bool {
+        self.emitting
+    }
+
+    /// True while emitting or while particles are alive
+    pub fn is_active(&self) -> bool {
+        self.emitting || !self.particles.is_empty()
+    }
+
+    pub fn particles_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+        self.vertices.clear();
+    }
+
+    /// Spawns particles now, in the limit of `max_particles`
+    pub fn emit(&mut self, count: u32) {
+        let count = (count as usize).min(self.config.max_particles.saturating_sub(self.particles.len()));
+        for _ in 0..count {
+            let particle = self.spawn_particle();
+            self.particles.push(particle);
+        }
+    }
+
+    fn spawn_particle(&mut self) -> Particle {
+        let config = &self.config;
+        let random = &mut self.random;
+
+        let offset = Vector2f::new(
+            (random.next() - 0.5) * config.spawn_area.x,
+            (random.next() - 0.5) * config.spawn_area.y
+        );
+        let angle = (config.direction + (random.next() - 0.5) * config.spread).to_radians();
+        let speed = random.range(config.speed);
+        let texture_rect = match config.texture_rects.len() {
+            0 => None,
+            len => Some(config.texture_rects[((random.next() * len as f32) as usize).min(len - 1)])
+        };
+
+        Particle {
+            position: self.position + offset,
+            velocity: Vector2f::new(angle.cos(), angle.sin()) * speed,
+            rotation: random.range(config.rotation),
+            angular_velocity: random.range(config.angular_velocity),
+            age: 0.0,
+            lifetime: random.range(config.lifetime).max(f32::EPSILON),
+            texture_rect
+        }
+    }
+
+    /// Spawns, moves and ages the particles, usually called with `StateData::delta_time`
+    pub fn update(&mut self, delta_time: f32) {
+        if self.emitting {
+            let mut emission_delta = delta_time;
+            self.emission_time += delta_time;
+            if self.config.duration > 0.0 && self.emission_time >= self.config.duration {
+                emission_delta -= self.emission_time - self.config.duration;
+                self.emitting = false;
+            }
+
+            self.spawn_accumulator += emission_delta.max(0.0) * self.config.spawn_rate;
+            let count = self.spawn_accumulator.floor();
+            self.spawn_accumulator -= count;
+            self.emit(count as u32);
+        }
+
+        let gravity = self.config.gravity;
+        let damping = (1.0 - self.config.damping * delta_time).max(0.0);
+        self.particles.retain_mut(|particle| {
+            particle.age += delta_time;
+            particle.velocity = (particle.velocity + gravity * delta_time) * damping;
+            particle.position += particle.velocity * delta_time;
+            particle.rotation += particle.angular_velocity * delta_time;
+            particle.age < particle.lifetime
+        });
+
+        self.update_vertices();
+    }
+
+    fn update_vertices(&mut self) {
+        self.vertices.clear();
+        for particle in self.particles.iter() {
+            let t = particle.age / particle.lifetime;
+            let color = sample(&self.config.colors, t, DEFAULT_COLOR);
+            let width = sample(&self.config.sizes, t, DEFAULT_SIZE);
+
+            let (size, tex_coords) = match particle.texture_rect {
+                Some(rect) => (
+                    Vector2f::new(width, width * rect.height as f32 / rect.width.max(1) as f32),
+                    rect.as_other::<f32>()
+                ),
+                None => (Vector2f::new(width, width), FloatRect::default())
+            };
+
+            let (sin, cos) = particle.rotation.to_radians().sin_cos();
+            let half = size / 2.0;
+            let corner = |x: f32, y: f32, u: f32, v: f32| Vertex::new(
+                particle.position + Vector2f::new(x * cos - y * sin, x * sin + y * cos),
+                color,
+                Vector2f::new(tex_coords.left + u * tex_coords.width, tex_coords.top + v * tex_coords.height)
+            );
+
+            // Same corners order as `get_quad_corners`
+            let corners = [
+                corner(-half.x, -half.y, 0.0, 0.0),
+                corner(-half.x, half.y, 0.0, 1.0),
+                corner(half.x, half.y, 1.0, 1.0),
+                corner(half.x, -half.y, 1.0, 0.0)
+            ];
+            self.vertices.extend(QUAD_INDICES.map(|i| corners[i]));
+        }
+    }
+
+    /// Same as `Drawable::draw` without its lifetimes constraints
+    ///
+    /// The emitter texture replaces the one of the states, the particles are drawn
+    /// without texture if it is gone.
+    pub fn render(&self, target: &mut dyn RenderTarget, states: &RenderStates) {
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        let mut states = *states;
+        if let Some(sprite) = &self.texture {
+            states.set_texture(sprite.texture());
+        }
+        target.draw_primitives(&self.vertices, PrimitiveType::TRIANGLES, &states);
+    }
+}
+
+impl Drawable for ParticleEmitter {
+    fn draw<'a: 'shader, 'texture, 'shader, 'shader_texture>(
+            &'a self,
+            target: &mut dyn RenderTarget,
+            states: &RenderStates<'texture, 'shader, 'shader_texture>,
+        ) {
+        self.render(target, states);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_interpolates_the_keys() {
+        let keys = [0.0, 10.0, 30.0];
+        assert_eq!(sample(&keys, 0.0, 1.0), 0.0);
+        assert_eq!(sample(&keys, 0.25, 1.0), 5.0);
+        assert_eq!(sample(&keys, 0.75, 1.0), 20.0);
+        assert_eq!(sample(&keys, 1.0, 1.0), 30.0);
+        assert_eq!(sample(&keys, 2.0, 1.0), 30.0);
+        assert_eq!(sample(&[7.0], 0.5, 1.0), 7.0);
+        assert_eq!(sample(&[], 0.5, 1.0), 1.0);
+    }
+
+    #[test]
+    fn empty_sizes_and_colors_use_the_defaults() {
+        let mut emitter = ParticleEmitter::new(ParticleConfig::default());
+        emitter.config_mut().sizes.clear();
+        emitter.config_mut().colors.clear();
+        emitter.emit(3);
+        emitter.update(0.1);
+
+        assert_eq!(emitter.particles_count(), 3);
+        assert!(emitter.vertices.iter().all(|vertex| vertex.color == DEFAULT_COLOR));
+    }
+
+    #[test]
+    fn parse_config() {
+        let config = ParticleConfig::parse(r##"
+            burst = 20
+            lifetime = [0.5, 1.5]
+            gravity = [0, 400]
+            sizes = [6, 0]
+            colors = ["#ff000080", [0, 255, 0]]
+            regions = ["spark"]
+        "##).unwrap();
+
+        assert_eq!(config.burst, 20);
+        assert_eq!(config.lifetime, (0.5, 1.5));
+        assert_eq!(config.gravity, Vector2f::new(0.0, 400.0));
+        assert_eq!(config.sizes, [6.0, 0.0]);
+        assert_eq!(config.colors, [Color::rgba(255, 0, 0, 128), Color::rgb(0, 255, 0)]);
+        assert_eq!(config.regions, ["spark"]);
+
+        assert!(ParticleConfig::parse("sizes = []").is_err());
+        assert!(ParticleConfig::parse("unknown = 1").is_err());
+    }
+}
@@ -0,0 +1,454 @@
+use crate::sfml_export::*;
+use crate::core::file_system::FileSystem;
+
+//////////////// Built-in shaders ////////////////////////
+
+const GRAYSCALE_SHADER: &str = r#"
+uniform sampler2D texture;
+uniform float amount;
+
+void main() {
+    vec4 color = texture2D(texture, gl_TexCoord[0].xy);
+    float gray = dot(color.rgb, vec3(0.299, 0.587, 0.114));
+    gl_FragColor = vec4(mix(color.rgb, vec3(gray), amount), color.a) * gl_Color;
+}
+"#;
+
+const VIGNETTE_SHADER: &str = r#"
+uniform sampler2D texture;
+uniform float radius;
+uniform float softness;
+uniform float intensity;
+
+void main() {
+    vec2 uv = gl_TexCoord[0].xy;
+    vec4 color = texture2D(texture, uv);
+    float vignette = smoothstep(radius, radius - softness, distance(uv, vec2(0.5)));
+    gl_FragColor = vec4(mix(color.rgb, color.rgb * vignette, intensity), color.a) * gl_Color;
+}
+"#;
+
+const CRT_SHADER: &str = r#"
+uniform sampler2D texture;
+uniform vec2 resolution;
+uniform float time;
+uniform float curvature;
+uniform float scanlines;
+uniform float flicker;
+
+void main() {
+    vec2 uv = gl_TexCoord[0].xy * 2.0 - 1.0;
+    uv += uv * uv.yx * uv.yx * curvature;
+    uv = uv * 0.5 + 0.5;
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+        gl_FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    float shift = 0.5 / resolution.x;
+    vec3 color = vec3(
+        texture2D(texture, uv + vec2(shift, 0.0)).r,
+        texture2D(texture, uv).g,
+        texture2D(texture, uv - vec2(shift, 0.0)).b
+    );
+    color *= 1.0 - scanlines * (sin(gl_FragCoord.y * 3.14159) * 0.5 + 0.5);
+    color *= 1.0 - flicker * (sin(time * 60.0) * 0.5 + 0.5);
+
+    vec2 edges = uv * (1.0 - uv.yx);
+    color *= pow(clamp(edges.x * edges.y * 15.0, 0.0, 1.0), 0.25);
+    gl_FragColor = vec4(color, 1.0) * gl_Color;
+}
+"#;
+
+const BLUR_SHADER: &str = r#"
+uniform sampler2D texture;
+uniform vec2 resolution;
+uniform float radius;
+
+void main() {
+    vec2 uv = gl_TexCoord[0].xy;
+    vec2 texel = radius / resolution;
+    vec4 sum = vec4(0.0);
+    float total = 0.0;
+    for (int x = -3; x <= 3; x++) {
+        for (int y = -3; y <= 3; y++) {
+            vec2 offset = vec2(float(x), float(y));
+            float weight = exp(-dot(offset, offset) / 8.0);
+            sum += texture2D(texture, uv + offset * texel) * weight;
+            total += weight;
+        }
+    }
+    gl_FragColor = sum / total * gl_Color;
+}
+"#;
+
+const BLOOM_SHADER: &str = r#"
+uniform sampler2D texture;
+uniform vec2 resolution;
+uniform float threshold;
+uniform float intensity;
+uniform float radius;
+
+void main() {
+    vec2 uv = gl_TexCoord[0].xy;
+    vec4 color = texture2D(texture, uv);
+    vec2 texel = radius / resolution;
+    vec3 bloom = vec3(0.0);
+    float total = 0.0;
+    for (int x = -3; x <= 3; x++) {
+        for (int y = -3; y <= 3; y++) {
+            vec2 offset = vec2(float(x), float(y));
+            float weight = exp(-dot(offset, offset) / 8.0);
+            vec3 neighbor = texture2D(texture, uv + offset * texel).rgb;
+            float brightness = dot(neighbor, vec3(0.299, 0.587, 0.114));
+            bloom += neighbor * smoothstep(threshold, 1.0, brightness) * weight;
+            total += weight;
+        }
+    }
+    gl_FragColor = vec4(color.rgb + bloom / total * intensity, color.a) * gl_Color;
+}
+"#;
+
+const COLOR_GRADING_SHADER: &str = r#"
+uniform sampler2D texture;
+uniform float brightness;
+uniform float contrast;
+uniform float saturation;
+uniform vec4 tint;
+
+void main() {
+    vec4 color = texture2D(texture, gl_TexCoord[0].xy);
+    vec3 graded = (color.rgb + brightness - 0.5) * contrast + 0.5;
+    graded = mix(vec3(dot(graded, vec3(0.299, 0.587, 0.114))), graded, saturation);
+    gl_FragColor = vec4(clamp(graded * tint.rgb, 0.0, 1.0), color.a) * gl_Color;
+}
+"#;
+
+//////////////// Pass ////////////////////////
+
+/// Fragment shader drawing its input texture, one step of a `PostProcess`
+///
+/// The shader receives its input as `uniform sampler2D texture`, and if it declares them
+/// `uniform vec2 resolution` with the input size in pixels and `uniform float time` in seconds.
+pub struct PostPass {
+    name: String,
+    shader: Shader<'static>,
+    enabled: bool,
+    uses_resolution: bool,
+    uses_time: bool
+}
+
+impl PostPass {
+    pub fn from_fragment(name: &str, source: &str) -> Result<Self, String> {
+        if !Shader::is_available() {
+            return Err("Shaders are not supported by the graphics card".to_string());
+        }
+        let shader = Shader::from_memory(source, ShaderType::Fragment)
+            .map_err(|e| format!("Cannot compile {} shader: {}", name, e))?;
+
+        Ok(Self {
+            name: name.to_string(),
+            shader,
+            enabled: true,
+            uses_resolution: source.contains("uniform vec2 resolution"),
+            uses_time: source.contains("uniform float time")
+        })
+    }
+
+    pub fn load(name: &str, file_system: &FileSystem, path: &str) -> Result<Self, String> {
+        Self::from_fragment(name, &file_system.read_to_string(path)?)
+    }
+
+    /// `amount` between 0 (colors) and 1 (gray)
+    pub fn grayscale() -> Result<Self, String> {
+        let mut pass = Self::from_fragment("grayscale", GRAYSCALE_SHADER)?;
+        pass.set_float("amount", 1.0);
+        Ok(pass)
+    }
+
+    /// Darkens from `radius` to `radius - softness` from the center, `intensity` between 0 and 1
+    pub fn vignette() -> Result<Self, String> {
+        let mut pass = Self::from_fragment("vignette", VIGNETTE_SHADER)?;
+        pass.set_float("radius", 0.75);
+        pass.set_float("softness", 0.45);
+        pass.set_float("intensity", 0.8);
+        Ok(pass)
+    }
+
+    /// Curved screen with `curvature`, `scanlines` and `flicker` intensities
+    pub fn crt() -> Result<Self, String> {
+        let mut pass = Self::from_fragment("crt", CRT_SHADER)?;
+        pass.set_float("curvature", 0.1);
+        pass.set_float("scanlines", 0.35);
+        pass.set_float("flicker", 0.03);
+        Ok(pass)
+    }
+
+    /// Gaussian blur, `radius` is the spacing of the samples in pixels
+    pub fn blur() -> Result<Self, String> {
+        let mut pass = Self::from_fragment("blur", BLUR_SHADER)?;
+        pass.set_float("radius", 2.0);
+        Ok(pass)
+    }
+
+    /// Glow of the colors brighter than `threshold`
+    pub fn bloom() -> Result<Self, String> {
+        let mut pass = Self::from_fragment("bloom", BLOOM_SHADER)?;
+        pass.set_float("threshold", 0.7);
+        pass.set_float("intensity", 1.0);
+        pass.set_float("radius", 2.0);
+        Ok(pass)
+    }
+
+    /// `brightness` is added, `contrast` and `saturation` are factors and the colors are multiplied by `tint`
+    pub fn color_grading() -> Result<Self, String> {
+        let mut pass = Self::from_fragment("color_grading", COLOR_GRADING_SHADER)?;
+        pass.set_float("brightness", 0.0);
+        pass.set_float("contrast", 1.0);
+        pass.set_float("saturation", 1.0);
+        pass.set_color("tint", Color::WHITE);
+        Ok(pass)
+    }
+
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn set_float(&mut self, uniform: &str, value: f32) {
+        self.shader.set_uniform_float(uniform, value);
+    }
+
+    pub fn set_vec2(&mut self, uniform: &str, value: Vector2f) {
+        self.shader.set_uniform_vec2(uniform, value);
+    }
+
+    pub fn set_color(&mut self, uniform: &str, color: Color) {
+        self.shader.set_uniform_vec4(uniform, color);
+    }
+
+    /// For the uniforms without setter
+    pub fn shader_mut(&mut self) -> &mut Shader<'static> {
+        &mut self.shader
+    }
+
+    fn draw(&mut self, input: &Texture, target: &mut dyn RenderTarget, states: &RenderStates, time: f32) {
+        self.shader.set_uniform_current_texture("texture");
+        if self.uses_resolution {
+            self.shader.set_uniform_vec2("resolution", input.size().as_other());
+        }
+        if self.uses_time {
+            self.shader.set_uniform_float("time", time);
+        }
+
+        let mut states = *states;
+        states.set_shader(Some(&self.shader));
+        target.draw_with_renderstates(&Sprite::with_texture(input), &states);
+    }
+}
+
+//////////////////////////////////////
+
+/// Keeps the render texture of the given size, recreated when the size changes
+fn sized_render_texture(render_texture: &mut Option<RenderTexture>, size: Vector2u) -> Result<&mut RenderTexture, String> {
+    if render_texture.as_ref().map(|render_texture| render_texture.size()) != Some(size) {
+        *render_texture = Some(RenderTexture::new(size.x, size.y)
+            .ok_or(format!("Cannot create a {}x{} render texture", size.x, size.y))?);
+    }
+    Ok(render_texture.as_mut().unwrap())
+}
+
+/// Chain of shader passes applied to a texture or to what is drawn through it
+///
+/// The enabled passes run in order, each one drawing the output of the previous one
+/// in an offscreen texture, the last one draws on the target.
+/// The passes are found by name to toggle or tune them at runtime.
+/// `StateData::post_process` is applied by the application to the whole frame when a pass is enabled.
+#[derive(Default)]
+pub struct PostProcess {
+    passes: Vec<PostPass>,
+    scene: Option<RenderTexture>,
+    buffers: [Option<RenderTexture>; 2],
+    time: f32,
+    error: Option<String>
+}
+
+impl PostProcess {
+    pub fn new() -> Self {
+        Self {
+            passes: Vec::new(),
+            scene: None,
+            buffers: [None, None],
+            time: 0.0,
+            error: None
+        }
+    }
+
+    pub fn with_pass(mut self, pass: PostPass) -> Self {
+        self.add_pass(pass);
+        self
+    }
+
+    pub fn add_pass(&mut self, pass: PostPass) {
+        self.passes.push(pass);
+    }
+
+    pub fn remove_pass(&mut self, name: &str) -> Option<PostPass> {
+        let index = self.passes.iter().position(|pass| pass.name == name)?;
+        Some(self.passes.remove(index))
+    }
+
+    pub fn pass(&self, name: &str) -> Option<&PostPass> {
+        self.passes.iter().find(|pass| pass.name == name)
+    }
+
+    pub fn pass_mut(&mut self, name: &str) -> Option<&mut PostPass> {
+        self.passes.iter_mut().find(|pass| pass.name == name)
+    }
+
+    pub fn passes(&self) -> &[PostPass] {
+        &self.passes
+    }
+
+    /// Returns false if there is no pass with this name
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        match self.pass_mut(name) {
+            Some(pass) => {
+                pass.set_enabled(enabled);
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Returns the new state, false if there is no pass with this name
+    pub fn toggle(&mut self, name: &str) -> bool {
+        match self.pass_mut(name) {
+            Some(pass) => {
+                pass.set_enabled(!pass.is_enabled());
+                pass.is_enabled()
+            },
+            None => false
+        }
+    }
+
+    /// Why the offscreen textures could not be created the last time it failed,
+    /// the passes are skipped and the source is drawn as is in that case
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// True if at least one pass is enabled
+    pub fn is_active(&self) -> bool {
+        self.passes.iter().any(|pass| pass.enabled)
+    }
+
+    /// Advances the `time` uniform
+    pub fn update(&mut self, delta_time: f32) {
+        self.time += delta_time;
+    }
+
+    /// Runs the passes over the source and draws the result on the target with the states
+    pub fn process(&mut self, source: &Texture, target: &mut dyn RenderTarget, states: &RenderStates) {
+        let enabled = self.passes.iter()
+            .enumerate()
+            .filter(|(_, pass)| pass.enabled)
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+
+        let (last, intermediates) = match enabled.split_last() {
+            Some(split) => split,
+            None => {
+                target.draw_with_renderstates(&Sprite::with_texture(source), states);
+                return;
+            }
+        };
+
+        let size = source.size();
+        let [front, back] = &mut self.buffers;
+        let (mut front, mut back) = (front, back);
+        let mut from_source = true;
+
+        // The intermediate textures are copied without blending
+        let mut copy_states = RenderStates::DEFAULT;
+        copy_states.blend_mode = BlendMode::NONE;
+
+        for &index in intermediates {
+            let output = match sized_render_texture(back, size) {
+                Ok(output) => output,
+                Err(error) => {
+                    self.error = Some(error);
+                    target.draw_with_renderstates(&Sprite::with_texture(source), states);
+                    return;
+                }
+            };
+            let input = if from_source { source } else { front.as_ref().unwrap().texture() };
+
+            output.clear(Color::TRANSPARENT);
+            self.passes[index].draw(input, output, &copy_states, self.time);
+            output.display();
+
+            std::mem::swap(&mut front, &mut back);
+            from_source = false;
+        }
+
+        let input = if from_source { source } else { front.as_ref().unwrap().texture() };
+        self.passes[*last].draw(input, target, states, self.time);
+    }
+
+    /// Offscreen texture with the size and view of the target, cleared, to draw a frame in
+    ///
+    /// Give it to `present` to run the passes, or to `end_scene` to skip the frame.
+    /// None if it cannot be created, see `error`.
+    pub fn begin_scene(&mut self, target: &dyn RenderTarget) -> Option<RenderTexture> {
+        if let Err(error) = sized_render_texture(&mut self.scene, target.size()) {
+            self.error = Some(error);
+            return None;
+        }
+        let mut scene = self.scene.take().unwrap();
+        scene.set_view(target.view());
+        scene.clear(Color::TRANSPARENT);
+        Some(scene)
+    }
+
+    /// Runs the passes over the scene of `begin_scene` and draws the result on the whole target
+    pub fn present(&mut self, scene: RenderTexture, target: &mut dyn RenderTarget) {
+        scene.display();
+
+        let size = target.size();
+        let view = target.view().to_owned();
+        target.set_view(&View::from_rect(FloatRect::new(0.0, 0.0, size.x as f32, size.y as f32)));
+        self.process(scene.texture(), target, &RenderStates::DEFAULT);
+        target.set_view(&view);
+
+        self.end_scene(scene);
+    }
+
+    /// Keeps the scene of `begin_scene` for the next frame
+    pub fn end_scene(&mut self, scene: RenderTexture) {
+        self.scene = Some(scene);
+    }
+
+    /// Draws offscreen with the view of the target, then runs the passes over the whole target
+    pub fn render<R>(&mut self, target: &mut dyn RenderTarget, draw: impl FnOnce(&mut dyn RenderTarget) -> R) -> R {
+        let mut scene = match self.begin_scene(target) {
+            Some(scene) => scene,
+            None => return draw(target)
+        };
+        let res = draw(&mut scene);
+        self.present(scene, target);
+        res
+    }
+}
@@ -0,0 +1,133 @@
+use std::cell::RefCell;
+use crate::sfml_export::*;
+use super::{get_quad_corners, QUAD_INDICES};
+
+/// Triangles of the quads, uploaded to the graphics card when they changed
+struct GpuMesh {
+    vertices: Vec<Vertex>,
+    buffer: Option<VertexBuffer>,
+    up_to_date: bool
+}
+
+/// Quads stored as their 4 corners and drawn from a `VertexBuffer`
+///
+/// The buffer is only uploaded again when the quads changed, drawing an unchanged mesh
+/// costs no vertex generation. Without vertex buffer support the triangles are drawn
+/// from memory instead.
+/// The texture of the quads is the one of the states given to draw.
+pub struct QuadMesh {
+    quads: Vec<[Vertex; 4]>,
+    usage: VertexBufferUsage,
+    gpu: RefCell<GpuMesh>
+}
+
+impl Default for QuadMesh {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QuadMesh {
+    pub fn new() -> Self {
+        Self {
+            quads: Vec::new(),
+            usage: VertexBufferUsage::STATIC,
+            gpu: RefCell::new(GpuMesh {
+                vertices: Vec::new(),
+                buffer: None,
+                up_to_date: true
+            })
+        }
+    }
+
+    /// `DYNAMIC` or `STREAM` for meshes changing often
+    pub fn with_usage(mut self, usage: VertexBufferUsage) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.quads.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.quads.is_empty()
+    }
+
+    pub fn quads(&self) -> &[[Vertex; 4]] {
+        &self.quads
+    }
+
+    pub fn clear(&mut self) {
+        self.quads.clear();
+        self.invalidate();
+    }
+
+    /// Adds the transformed unit rect, see `get_quad_corners`
+    pub fn push_quad(&mut self, transform: &Transform, color: Color, tex_coords: FloatRect) {
+        self.push_corners(get_quad_corners(transform, color, tex_coords));
+    }
+
+    pub fn push_corners(&mut self, corners: [Vertex; 4]) {
+        self.quads.push(corners);
+        self.invalidate();
+    }
+
+    pub fn set_corners(&mut self, index: usize, corners: [Vertex; 4]) {
+        self.quads[index] = corners;
+        self.invalidate();
+    }
+
+    fn invalidate(&mut self) {
+        self.gpu.get_mut().up_to_date = false;
+    }
+
+    /// Same as `Drawable::draw` without its lifetimes constraints
+    pub fn render(&self, target: &mut dyn RenderTarget, states: &RenderStates) {
+        if self.quads.is_empty() {
+            return;
+        }
+
+        let mut gpu = self.gpu.borrow_mut();
+        if !gpu.up_to_date {
+            self.upload(&mut gpu);
+        }
+
+        match &gpu.buffer {
+            Some(buffer) => target.draw_vertex_buffer(buffer, states),
+            None => target.draw_primitives(&gpu.vertices, PrimitiveType::TRIANGLES, states)
+        }
+    }
+
+    fn upload(&self, gpu: &mut GpuMesh) {
+        gpu.vertices.clear();
+        gpu.vertices.extend(self.quads.iter().flat_map(|corners| QUAD_INDICES.map(|i| corners[i])));
+        gpu.up_to_date = true;
+
+        if !VertexBuffer::available() {
+            gpu.buffer = None;
+            return;
+        }
+
+        // The whole buffer is drawn, it is recreated when the number of vertices changes
+        let vertex_count = gpu.vertices.len() as u32;
+        if gpu.buffer.as_ref().map(|buffer| buffer.vertex_count()) != Some(vertex_count) {
+            gpu.buffer = Some(VertexBuffer::new(PrimitiveType::TRIANGLES, vertex_count, self.usage));
+        }
+
+        let uploaded = gpu.buffer.as_mut().is_some_and(|buffer| buffer.update(&gpu.vertices, 0));
+        if !uploaded {
+            gpu.buffer = None;
+        }
+    }
+}
+
+impl Drawable for QuadMesh {
+    fn draw<'a: 'shader, 'texture, 'shader, 'shader_texture>(
+            &'a self,
+            target: &mut dyn RenderTarget,
+            states: &RenderStates<'texture, 'shader, 'shader_texture>,
+        ) {
+        self.render(target, states);
+    }
+}
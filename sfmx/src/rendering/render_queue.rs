@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use crate::sfml_export::*;
+
+/// Layers are drawn in increasing order, these are the usual ones
+pub const WORLD_LAYER: i32 = 0;
+pub const UI_LAYER: i32 = 100;
+pub const DEBUG_LAYER: i32 = 200;
+
+type DrawFn<'a> = Box<dyn FnOnce(&mut dyn RenderTarget) + 'a>;
+
+enum DrawCommand<'a> {
+    Drawable(&'a dyn Drawable, RenderStates<'a, 'a, 'a>),
+    Function(DrawFn<'a>)
+}
+
+struct QueuedDraw<'a> {
+    layer: i32,
+    z: f32,
+    command: DrawCommand<'a>
+}
+
+/// Draws submitted with a layer and a z value, drawn sorted when the queue is flushed
+///
+/// Usually created at the start of `on_render` and flushed at its end, so independent
+/// systems can submit their draws without coordinating their order.
+/// The draws are sorted by layer then by z, the draws with the same layer and z keep their order.
+/// Each layer is drawn with its own view, the default view of the target when it has none.
+pub struct RenderQueue<'a> {
+    draws: Vec<QueuedDraw<'a>>,
+    views: HashMap<i32, SfBox<View>>
+}
+
+impl<'a> Default for RenderQueue<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> RenderQueue<'a> {
+    pub fn new() -> Self {
+        Self {
+            draws: Vec::new(),
+            views: HashMap::new()
+        }
+    }
+
+    /// A copy of the view is kept, None draws the layer with the default view
+    pub fn set_layer_view(&mut self, layer: i32, view: Option<&View>) {
+        match view {
+            Some(view) => self.views.insert(layer, view.to_owned()),
+            None => self.views.remove(&layer)
+        };
+    }
+
+    pub fn submit(&mut self, layer: i32, z: f32, drawable: &'a dyn Drawable) {
+        self.submit_with_states(layer, z, drawable, RenderStates::DEFAULT);
+    }
+
+    pub fn submit_with_states(&mut self, layer: i32, z: f32, drawable: &'a dyn Drawable, states: RenderStates<'a, 'a, 'a>) {
+        self.push(layer, z, DrawCommand::Drawable(drawable, states));
+    }
+
+    /// For the systems drawing themselves on a target, like `UiManager::draw`
+    pub fn submit_fn(&mut self, layer: i32, z: f32, draw: impl FnOnce(&mut dyn RenderTarget) + 'a) {
+        self.push(layer, z, DrawCommand::Function(Box::new(draw)));
+    }
+
+    fn push(&mut self, layer: i32, z: f32, command: DrawCommand<'a>) {
+        self.draws.push(QueuedDraw {
+            layer,
+            z,
+            command
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.draws.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    /// Draws everything submitted then restores the view of the target
+    pub fn flush(&mut self, target: &mut dyn RenderTarget) {
+        let previous_view = target.view().to_owned();
+        let default_view = target.default_view().to_owned();
+
+        // Stable sort, the submission order is kept for equal layers and z
+        self.draws.sort_by(|a, b| a.layer.cmp(&b.layer).then(a.z.total_cmp(&b.z)));
+
+        let mut current_layer = None;
+        for draw in self.draws.drain(..) {
+            if current_layer != Some(draw.layer) {
+                current_layer = Some(draw.layer);
+                target.set_view(self.views.get(&draw.layer).unwrap_or(&default_view));
+            }
+
+            match draw.command {
+                DrawCommand::Drawable(drawable, states) => target.draw_with_renderstates(drawable, &states),
+                DrawCommand::Function(draw) => draw(target)
+            }
+        }
+
+        target.set_view(&previous_view);
+    }
+}
@@ -0,0 +1,511 @@
+use std::f32::consts::PI;
+use crate::sfml_export::*;
+
+/// Distance in pixels allowed between a curve and its segments
+const CURVE_TOLERANCE: f32 = 0.25;
+/// Longest miter of a join, in line widths, beyond it the join is beveled as in SVG
+const MITER_LIMIT: f32 = 4.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineCap {
+    /// Stops at the end points
+    Butt,
+    /// Extends past the end points by half the thickness
+    Square,
+    Round
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineJoin {
+    /// Sharp corners, beveled when too long
+    Miter,
+    Bevel,
+    Round
+}
+
+fn normalized(v: Vector2f) -> Vector2f {
+    let length = v.length_sq().sqrt();
+    if length > 0.0 { v / length } else { v }
+}
+
+/// Left normal of the direction
+fn normal(direction: Vector2f) -> Vector2f {
+    Vector2f::new(-direction.y, direction.x)
+}
+
+fn from_angle(degrees: f32) -> Vector2f {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    Vector2f::new(cos, sin)
+}
+
+/// Segments approximating an arc of the radius and sweep in degrees
+fn arc_segments(radius: f32, sweep: f32) -> usize {
+    let step = if radius > CURVE_TOLERANCE { 2.0 * (1.0 - CURVE_TOLERANCE / radius).acos() } else { PI / 2.0 };
+    ((sweep.abs().to_radians() / step).ceil() as usize).clamp(1, 256)
+}
+
+/// Points of an arc, both ends included
+fn arc_points(center: Vector2f, radius: f32, start_angle: f32, sweep: f32) -> Vec<Vector2f> {
+    let segments = arc_segments(radius, sweep);
+    (0..=segments)
+        .map(|i| center + from_angle(start_angle + sweep * i as f32 / segments as f32) * radius)
+        .collect()
+}
+
+/// Thickness, color and style of the lines
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stroke {
+    pub thickness: f32,
+    pub color: Color,
+    pub cap: LineCap,
+    pub join: LineJoin,
+    /// Lengths of the dashes and of the gaps between them, None draws a solid line
+    pub dash: Option<(f32, f32)>
+}
+
+impl Stroke {
+    pub fn new(thickness: f32, color: Color) -> Self {
+        Self {
+            thickness,
+            color,
+            cap: LineCap::Butt,
+            join: LineJoin::Miter,
+            dash: None
+        }
+    }
+
+    pub fn with_cap(mut self, cap: LineCap) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    pub fn with_join(mut self, join: LineJoin) -> Self {
+        self.join = join;
+        self
+    }
+
+    /// Each dash gets the caps of the stroke
+    pub fn with_dash(mut self, dash: f32, gap: f32) -> Self {
+        self.dash = Some((dash, gap));
+        self
+    }
+}
+
+/// Vector shapes generated as triangles in a single vertex array
+///
+/// Shapes are added with their color in target coordinates and drawn together in one
+/// draw call, like the sprites of a `SpriteBatch`. The angles are in degrees,
+/// 0 pointing right and 90 down. The strokes overlap at their joins, translucent
+/// strokes are darker there.
+#[derive(Clone, Default)]
+pub struct ShapeBatch {
+    vertices: Vec<Vertex>
+}
+
+impl ShapeBatch {
+    pub fn new() -> Self {
+        Self {
+            vertices: Vec::new()
+        }
+    }
+
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    fn triangle(&mut self, a: Vector2f, b: Vector2f, c: Vector2f, color: Color) {
+        self.vertices.extend([a, b, c].map(|position| Vertex::with_pos_color(position, color)));
+    }
+
+    fn quad(&mut self, a: Vector2f, b: Vector2f, c: Vector2f, d: Vector2f, color: Color) {
+        self.triangle(a, b, c, color);
+        self.triangle(c, d, a, color);
+    }
+
+    /// Triangle fan around the center, the points going around it
+    fn fan(&mut self, center: Vector2f, points: &[Vector2f], color: Color) {
+        for pair in points.windows(2) {
+            self.triangle(center, pair[0], pair[1], color);
+        }
+    }
+
+    //////////////// Strokes ////////////////////////
+
+    pub fn line(&mut self, start: Vector2f, end: Vector2f, stroke: Stroke) {
+        self.stroke(&[start, end], stroke, false);
+    }
+
+    /// Open line through the points
+    pub fn polyline(&mut self, points: &[Vector2f], stroke: Stroke) {
+        self.stroke(points, stroke, false);
+    }
+
+    /// Line through the points going back to the first one, without caps
+    pub fn closed_polyline(&mut self, points: &[Vector2f], stroke: Stroke) {
+        self.stroke(points, stroke, true);
+    }
+
+    /// Stroke of the circle part starting at `start_angle`, turning clockwise when `sweep` is positive
+    pub fn arc(&mut self, center: Vector2f, radius: f32, start_angle: f32, sweep: f32, stroke: Stroke) {
+        if sweep.abs() >= 360.0 {
+            let mut points = arc_points(center, radius, start_angle, 360.0);
+            points.pop();
+            self.closed_polyline(&points, stroke);
+        }
+        else {
+            self.polyline(&arc_points(center, radius, start_angle, sweep), stroke);
+        }
+    }
+
+    fn stroke(&mut self, points: &[Vector2f], stroke: Stroke, closed: bool) {
+        match stroke.dash {
+            Some((dash, gap)) if dash > 0.0 => {
+                let mut path = points.to_vec();
+                if closed && !points.is_empty() {
+                    path.push(points[0]);
+                }
+                self.dashes(&path, stroke, dash, gap);
+            },
+            _ => self.solid_stroke(points, stroke, closed)
+        }
+    }
+
+    /// Dashes along the points, the pattern goes on across the corners
+    fn dashes(&mut self, points: &[Vector2f], stroke: Stroke, dash: f32, gap: f32) {
+        // Distance left in the current dash or gap
+        let mut in_dash = true;
+        let mut left = dash;
+        let mut current_dash = Vec::new();
+        for pair in points.windows(2) {
+            let (mut from, to) = (pair[0], pair[1]);
+            let direction = normalized(to - from);
+            let mut length = (to - from).length_sq().sqrt();
+
+            while length > 0.0 {
+                let step = left.min(length);
+                let next = from + direction * step;
+                if in_dash {
+                    if current_dash.is_empty() {
+                        current_dash.push(from);
+                    }
+                    current_dash.push(next);
+                }
+                from = next;
+                length -= step;
+                left -= step;
+
+                if left <= 0.0 {
+                    if in_dash {
+                        self.solid_stroke(&current_dash, stroke, false);
+                        current_dash.clear();
+                    }
+                    in_dash = !in_dash || gap <= 0.0;
+                    left = if in_dash { dash } else { gap };
+                }
+            }
+        }
+
+        if current_dash.len() > 1 {
+            self.solid_stroke(&current_dash, stroke, false);
+        }
+    }
+
+    fn solid_stroke(&mut self, points: &[Vector2f], stroke: Stroke, closed: bool) {
+        let mut points = points.to_vec();
+        points.dedup_by(|a, b| (*a - *b).length_sq() <= f32::EPSILON);
+        if closed && points.len() > 2 && (points[0] - points[points.len() - 1]).length_sq() <= f32::EPSILON {
+            points.pop();
+        }
+        if points.len() < 2 || stroke.thickness <= 0.0 {
+            return;
+        }
+
+        let half = stroke.thickness / 2.0;
+        let count = points.len();
+        let cap = if closed { LineCap::Butt } else { stroke.cap };
+
+        if cap == LineCap::Square {
+            let start_direction = normalized(points[1] - points[0]);
+            let end_direction = normalized(points[count - 1] - points[count - 2]);
+            points[0] -= start_direction * half;
+            points[count - 1] += end_direction * half;
+        }
+
+        let segments_count = if closed { count } else { count - 1 };
+        for i in 0..segments_count {
+            let (a, b) = (points[i], points[(i + 1) % count]);
+            let offset = normal(normalized(b - a)) * half;
+            self.quad(a + offset, b + offset, b - offset, a - offset, stroke.color);
+        }
+
+        let joins = if closed { 0..count } else { 1..count - 1 };
+        for i in joins {
+            let previous = points[(i + count - 1) % count];
+            let next = points[(i + 1) % count];
+            self.join(previous, points[i], next, stroke);
+        }
+
+        if cap == LineCap::Round {
+            let start_direction = normalized(points[0] - points[1]);
+            let end_direction = normalized(points[count - 1] - points[count - 2]);
+            self.round_cap(points[0], start_direction, half, stroke.color);
+            self.round_cap(points[count - 1], end_direction, half, stroke.color);
+        }
+    }
+
+    /// Fills the gap on the outer side of the corner
+    fn join(&mut self, previous: Vector2f, point: Vector2f, next: Vector2f, stroke: Stroke) {
+        let in_direction = normalized(point - previous);
+        let out_direction = normalized(next - point);
+        let turn = in_direction.cross(out_direction);
+        if turn.abs() <= f32::EPSILON && in_direction.dot(out_direction) > 0.0 {
+            return;
+        }
+
+        // The outer side is the one opposite to the turn
+        let half = stroke.thickness / 2.0;
+        let side = if turn > 0.0 { -1.0 } else { 1.0 };
+        let in_offset = normal(in_direction) * half * side;
+        let out_offset = normal(out_direction) * half * side;
+        let color = stroke.color;
+
+        match stroke.join {
+            LineJoin::Miter => {
+                let miter = normalized(in_offset + out_offset);
+                let cos = miter.dot(in_offset) / half;
+                if cos > 0.0 && 1.0 / cos <= MITER_LIMIT {
+                    let tip = point + miter * (half / cos);
+                    self.quad(point, point + in_offset, tip, point + out_offset, color);
+                }
+                else {
+                    self.triangle(point, point + in_offset, point + out_offset, color);
+                }
+            },
+            LineJoin::Bevel => self.triangle(point, point + in_offset, point + out_offset, color),
+            LineJoin::Round => {
+                let start_angle = in_offset.y.atan2(in_offset.x).to_degrees();
+                let mut sweep = out_offset.y.atan2(out_offset.x).to_degrees() - start_angle;
+                if sweep > 180.0 {
+                    sweep -= 360.0;
+                }
+                else if sweep < -180.0 {
+                    sweep += 360.0;
+                }
+                self.fan(point, &arc_points(point, half, start_angle, sweep), color);
+            }
+        }
+    }
+
+    /// Half disc at the end point, on the side of the direction
+    fn round_cap(&mut self, point: Vector2f, direction: Vector2f, half: f32, color: Color) {
+        let start_angle = direction.y.atan2(direction.x).to_degrees() - 90.0;
+        self.fan(point, &arc_points(point, half, start_angle, 180.0), color);
+    }
+
+    //////////////// Fills ////////////////////////
+
+    /// Filled polygon, the points going around it
+    pub fn convex_polygon(&mut self, points: &[Vector2f], color: Color) {
+        if points.len() < 3 {
+            return;
+        }
+        for pair in points[1..].windows(2) {
+            self.triangle(points[0], pair[0], pair[1], color);
+        }
+    }
+
+    /// Filled circle part between the center and the arc
+    pub fn pie(&mut self, center: Vector2f, radius: f32, start_angle: f32, sweep: f32, color: Color) {
+        let sweep = sweep.clamp(-360.0, 360.0);
+        self.fan(center, &arc_points(center, radius, start_angle, sweep), color);
+    }
+
+    pub fn circle(&mut self, center: Vector2f, radius: f32, color: Color) {
+        self.pie(center, radius, 0.0, 360.0, color);
+    }
+
+    pub fn rounded_rect(&mut self, rect: FloatRect, radius: f32, color: Color) {
+        let points = Self::rounded_rect_points(rect, radius);
+        self.convex_polygon(&points, color);
+    }
+
+    /// Stroke centered on the border of the rect
+    pub fn rounded_rect_outline(&mut self, rect: FloatRect, radius: f32, stroke: Stroke) {
+        let points = Self::rounded_rect_points(rect, radius);
+        self.closed_polyline(&points, stroke);
+    }
+
+    /// Border of the rect, clockwise from the top left corner
+    fn rounded_rect_points(rect: FloatRect, radius: f32) -> Vec<Vector2f> {
+        let radius = radius.clamp(0.0, rect.width.abs().min(rect.height.abs()) / 2.0);
+        let (left, top) = (rect.left + radius, rect.top + radius);
+        let (right, bottom) = (rect.left + rect.width - radius, rect.top + rect.height - radius);
+
+        if radius == 0.0 {
+            return vec![
+                Vector2f::new(left, top),
+                Vector2f::new(right, top),
+                Vector2f::new(right, bottom),
+                Vector2f::new(left, bottom)
+            ];
+        }
+
+        let mut points = Vec::new();
+        for (corner, start_angle) in [((left, top), 180.0), ((right, top), 270.0), ((right, bottom), 0.0), ((left, bottom), 90.0)] {
+            points.extend(arc_points(Vector2f::from(corner), radius, start_angle, 90.0));
+        }
+        points
+    }
+
+    //////////////////////////////////////
+
+    /// Same as `Drawable::draw` without its lifetimes constraints
+    pub fn render(&self, target: &mut dyn RenderTarget, states: &RenderStates) {
+        if !self.vertices.is_empty() {
+            target.draw_primitives(&self.vertices, PrimitiveType::TRIANGLES, states);
+        }
+    }
+}
+
+impl Drawable for ShapeBatch {
+    fn draw<'a: 'shader, 'texture, 'shader, 'shader_texture>(
+            &'a self,
+            target: &mut dyn RenderTarget,
+            states: &RenderStates<'texture, 'shader, 'shader_texture>,
+        ) {
+        self.render(target, states);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stroke(thickness: f32) -> Stroke {
+        Stroke::new(thickness, Color::WHITE)
+    }
+
+    fn point(x: f32, y: f32) -> Vector2f {
+        Vector2f::new(x, y)
+    }
+
+    /// Left, top, right and bottom of the vertices
+    fn bounds(vertices: &[Vertex]) -> (f32, f32, f32, f32) {
+        vertices.iter().fold((f32::MAX, f32::MAX, f32::MIN, f32::MIN), |(left, top, right, bottom), vertex| {
+            let position = vertex.position;
+            (left.min(position.x), top.min(position.y), right.max(position.x), bottom.max(position.y))
+        })
+    }
+
+    fn assert_bounds(vertices: &[Vertex], expected: (f32, f32, f32, f32)) {
+        assert_bounds_within(vertices, expected, 1e-4);
+    }
+
+    /// The curves are inside their exact bounds, by less than the tolerance
+    fn assert_curve_bounds(vertices: &[Vertex], expected: (f32, f32, f32, f32)) {
+        assert_bounds_within(vertices, expected, CURVE_TOLERANCE);
+    }
+
+    fn assert_bounds_within(vertices: &[Vertex], expected: (f32, f32, f32, f32), tolerance: f32) {
+        let bounds = bounds(vertices);
+        let inside = |min: f32, value: f32, max: f32| value >= min - 1e-4 && value <= max + 1e-4;
+        assert!(
+            inside(expected.0, bounds.0, expected.0 + tolerance)
+                && inside(expected.1, bounds.1, expected.1 + tolerance)
+                && inside(expected.2 - tolerance, bounds.2, expected.2)
+                && inside(expected.3 - tolerance, bounds.3, expected.3),
+            "expected {:?}, got {:?}", expected, bounds
+        );
+    }
+
+    #[test]
+    fn line_caps() {
+        let mut batch = ShapeBatch::new();
+        batch.line(point(0.0, 0.0), point(10.0, 0.0), stroke(2.0));
+        assert_eq!(batch.vertices().len(), 6);
+        assert_bounds(batch.vertices(), (0.0, -1.0, 10.0, 1.0));
+
+        batch.clear();
+        batch.line(point(0.0, 0.0), point(10.0, 0.0), stroke(2.0).with_cap(LineCap::Square));
+        assert_eq!(batch.vertices().len(), 6);
+        assert_bounds(batch.vertices(), (-1.0, -1.0, 11.0, 1.0));
+
+        batch.clear();
+        batch.line(point(0.0, 0.0), point(10.0, 0.0), stroke(2.0).with_cap(LineCap::Round));
+        assert!(batch.vertices().len() > 6);
+        assert_curve_bounds(batch.vertices(), (-1.0, -1.0, 11.0, 1.0));
+    }
+
+    #[test]
+    fn miter_join_tip_and_limit() {
+        let mut batch = ShapeBatch::new();
+        batch.polyline(&[point(0.0, 0.0), point(10.0, 0.0), point(10.0, 10.0)], stroke(2.0));
+        // Two segments and the miter quad
+        assert_eq!(batch.vertices().len(), 18);
+        let tip = batch.vertices()[12..].iter().any(|vertex| (vertex.position - point(11.0, -1.0)).length_sq() < 1e-6);
+        assert!(tip, "no miter tip at (11, -1)");
+
+        // Turning almost back goes over the miter limit, the join is beveled
+        batch.clear();
+        batch.polyline(&[point(0.0, 0.0), point(10.0, 0.0), point(0.0, 1.0)], stroke(2.0));
+        assert_eq!(batch.vertices().len(), 15);
+    }
+
+    #[test]
+    fn closed_polyline_joins_every_corner() {
+        let mut batch = ShapeBatch::new();
+        let square = [point(0.0, 0.0), point(10.0, 0.0), point(10.0, 10.0), point(0.0, 10.0)];
+        batch.closed_polyline(&square, stroke(2.0).with_cap(LineCap::Square));
+        assert_eq!(batch.vertices().len(), 4 * 6 + 4 * 6);
+        assert_bounds(batch.vertices(), (-1.0, -1.0, 11.0, 11.0));
+    }
+
+    #[test]
+    fn dashes_along_a_line() {
+        let mut batch = ShapeBatch::new();
+        batch.line(point(0.0, 0.0), point(100.0, 0.0), stroke(2.0).with_dash(10.0, 10.0));
+        assert_eq!(batch.vertices().len(), 30);
+        for (i, dash) in batch.vertices().chunks(6).enumerate() {
+            let start = i as f32 * 20.0;
+            assert_bounds(dash, (start, -1.0, start + 10.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn dash_pattern_goes_on_across_a_corner() {
+        let mut batch = ShapeBatch::new();
+        let stroke = stroke(2.0).with_join(LineJoin::Bevel).with_dash(6.0, 2.0);
+        batch.polyline(&[point(0.0, 0.0), point(10.0, 0.0), point(10.0, 20.0)], stroke);
+
+        // The second dash turns the corner: two segments and a bevel
+        let vertices = batch.vertices();
+        assert_eq!(vertices.len(), 6 + 15 + 6 + 6);
+        assert_bounds(&vertices[0..6], (0.0, -1.0, 6.0, 1.0));
+        assert_bounds(&vertices[6..21], (8.0, -1.0, 11.0, 4.0));
+        assert_bounds(&vertices[21..27], (9.0, 6.0, 11.0, 12.0));
+        assert_bounds(&vertices[27..33], (9.0, 14.0, 11.0, 20.0));
+    }
+
+    #[test]
+    fn fills() {
+        let mut batch = ShapeBatch::new();
+        batch.convex_polygon(&[point(0.0, 0.0), point(10.0, 0.0), point(10.0, 10.0), point(0.0, 10.0)], Color::WHITE);
+        assert_eq!(batch.vertices().len(), 6);
+
+        batch.clear();
+        batch.circle(point(5.0, 5.0), 10.0, Color::WHITE);
+        assert_curve_bounds(batch.vertices(), (-5.0, -5.0, 15.0, 15.0));
+
+        batch.clear();
+        batch.rounded_rect(FloatRect::new(0.0, 0.0, 40.0, 20.0), 5.0, Color::WHITE);
+        assert_bounds(batch.vertices(), (0.0, 0.0, 40.0, 20.0));
+    }
+}
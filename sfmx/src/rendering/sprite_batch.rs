@@ -0,0 +1,229 @@
+use crate::sfml_export::*;
+use super::{get_quad, AtlasRegion, TextureAtlas};
+
+/// Draw calls of the last flush of a `SpriteBatch`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BatchStats {
+    pub quads: usize,
+    pub draw_calls: usize,
+    /// Draw calls that drawing each quad on its own would have added
+    pub saved_draw_calls: usize
+}
+
+#[derive(Clone, Copy)]
+enum BatchTexture<'a> {
+    None,
+    Borrowed(&'a Texture),
+    /// Index of the sprite keeping a shared texture in the batch
+    Shared(usize)
+}
+
+struct BatchItem<'a> {
+    texture: BatchTexture<'a>,
+    /// Order in which the texture first appeared in the batch, 0 without texture
+    texture_order: usize,
+    blend_mode: BlendMode,
+    depth: f32,
+    vertices: [Vertex; 6]
+}
+
+impl<'a> BatchItem<'a> {
+    fn texture<'s>(&self, shared: &'s [(u32, RcSprite)]) -> Option<&'s Texture> where 'a: 's {
+        match self.texture {
+            BatchTexture::None => None,
+            BatchTexture::Borrowed(texture) => Some(texture),
+            BatchTexture::Shared(index) => shared[index].1.texture()
+        }
+    }
+}
+
+/// Accumulates quads and draws the ones sharing a texture and a blend mode with a single draw call
+///
+/// The quads are sorted by depth, the lower ones are drawn first, then by texture
+/// in the order the textures were first added.
+/// Quads of the same depth can therefore be drawn in a different order than they were added.
+///
+/// Shared textures (`RcTexture`, atlas pages, `RcSprite`s) are not borrowed, they must
+/// still be alive when the batch is flushed or their quads are drawn without texture.
+pub struct SpriteBatch<'a> {
+    items: Vec<BatchItem<'a>>,
+    /// Native handle of the shared textures and a sprite keeping a weak reference to each
+    shared: Vec<(u32, RcSprite)>,
+    /// Native handle of the textures in the order they were first added
+    textures: Vec<u32>,
+    vertices: Vec<Vertex>,
+    blend_mode: BlendMode,
+    depth: f32,
+    stats: BatchStats
+}
+
+impl<'a> Default for SpriteBatch<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> SpriteBatch<'a> {
+    pub fn new() -> Self {
+        Self {
+            items: Vec::new(),
+            shared: Vec::new(),
+            textures: Vec::new(),
+            vertices: Vec::new(),
+            blend_mode: BlendMode::ALPHA,
+            depth: 0.0,
+            stats: BatchStats::default()
+        }
+    }
+
+    /// Blend mode of the quads added after this call
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
+
+    /// Depth of the quads added after this call
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth;
+    }
+
+    /// Adds the `tex_rect` part of the texture, the transform is applied to a rect of the size of `tex_rect`
+    pub fn draw_texture(&mut self, texture: &'a Texture, transform: &Transform, tex_rect: IntRect, color: Color) {
+        self.push_textured(BatchTexture::Borrowed(texture), transform, tex_rect, color);
+    }
+
+    /// Adds the whole texture, the transform is applied to a rect of the size of the texture
+    pub fn draw_whole_texture(&mut self, texture: &'a Texture, transform: &Transform, color: Color) {
+        let size = texture.size().as_other::<i32>();
+        self.draw_texture(texture, transform, IntRect::new(0, 0, size.x, size.y), color);
+    }
+
+    /// Same as `draw_texture` with a shared texture, such as an assets manager texture
+    pub fn draw_rc_texture(&mut self, texture: &RcTexture, transform: &Transform, tex_rect: IntRect, color: Color) {
+        let handle = texture.native_handle();
+        let index = self.shared_index(handle, || RcSprite::with_texture(texture));
+        self.push_textured(BatchTexture::Shared(index), transform, tex_rect, color);
+    }
+
+    pub fn draw_whole_rc_texture(&mut self, texture: &RcTexture, transform: &Transform, color: Color) {
+        let size = texture.size().as_other::<i32>();
+        self.draw_rc_texture(texture, transform, IntRect::new(0, 0, size.x, size.y), color);
+    }
+
+    /// Adds the region of its atlas page, the transform is applied to a rect of the size of the region
+    pub fn draw_atlas_region(&mut self, atlas: &TextureAtlas, region: AtlasRegion, transform: &Transform, color: Color) {
+        self.draw_rc_texture(atlas.page(region.page), transform, region.rect, color);
+    }
+
+    /// Adds the sprite with its transform, texture rect and color, nothing if its texture is gone
+    pub fn draw_rc_sprite(&mut self, sprite: &RcSprite) {
+        let handle = match sprite.texture() {
+            Some(texture) => texture.native_handle(),
+            None => return
+        };
+        let index = self.shared_index(handle, || sprite.clone());
+        self.push_textured(BatchTexture::Shared(index), sprite.transform(), sprite.texture_rect(), sprite.color());
+    }
+
+    /// Adds a quad generated by `get_quad`, without texture it is filled with the vertices colors
+    pub fn draw_quad(&mut self, texture: Option<&'a Texture>, vertices: [Vertex; 6]) {
+        self.push(texture.map_or(BatchTexture::None, BatchTexture::Borrowed), vertices);
+    }
+
+    fn shared_index(&mut self, handle: u32, sprite: impl FnOnce() -> RcSprite) -> usize {
+        match self.shared.iter().position(|(shared_handle, _)| *shared_handle == handle) {
+            Some(index) => index,
+            None => {
+                self.shared.push((handle, sprite()));
+                self.shared.len() - 1
+            }
+        }
+    }
+
+    fn push_textured(&mut self, texture: BatchTexture<'a>, transform: &Transform, tex_rect: IntRect, color: Color) {
+        let tex_rect = tex_rect.as_other::<f32>();
+        let mut transform = *transform;
+        transform.scale(tex_rect.width, tex_rect.height);
+        self.push(texture, get_quad(&transform, color, tex_rect));
+    }
+
+    fn texture_order(&mut self, texture: BatchTexture<'a>) -> usize {
+        let handle = match texture {
+            BatchTexture::None => return 0,
+            BatchTexture::Borrowed(texture) => texture.native_handle(),
+            BatchTexture::Shared(index) => self.shared[index].0
+        };
+        match self.textures.iter().position(|texture_handle| *texture_handle == handle) {
+            Some(index) => index + 1,
+            None => {
+                self.textures.push(handle);
+                self.textures.len()
+            }
+        }
+    }
+
+    fn push(&mut self, texture: BatchTexture<'a>, vertices: [Vertex; 6]) {
+        let texture_order = self.texture_order(texture);
+        self.items.push(BatchItem {
+            texture,
+            texture_order,
+            blend_mode: self.blend_mode,
+            depth: self.depth,
+            vertices
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Removes the quads without drawing them
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.shared.clear();
+        self.textures.clear();
+    }
+
+    /// Draws the quads and removes them from the batch, the states texture and blend mode are replaced
+    pub fn flush(&mut self, target: &mut dyn RenderTarget, states: &RenderStates) {
+        // Stable sort, the quads of a same depth and texture keep their order
+        let shared = &self.shared;
+        self.items.sort_by(|a, b| a.depth.total_cmp(&b.depth).then(a.texture_order.cmp(&b.texture_order)));
+
+        let mut stats = BatchStats {
+            quads: self.items.len(),
+            ..Default::default()
+        };
+
+        let mut start = 0;
+        while start < self.items.len() {
+            let first = &self.items[start];
+            let end = self.items[start..].iter()
+                .position(|item| item.texture_order != first.texture_order || item.blend_mode != first.blend_mode)
+                .map_or(self.items.len(), |len| start + len);
+
+            self.vertices.clear();
+            self.vertices.extend(self.items[start..end].iter().flat_map(|item| item.vertices));
+
+            let mut batch_states = *states;
+            batch_states.set_texture(first.texture(shared));
+            batch_states.blend_mode = first.blend_mode;
+            target.draw_primitives(&self.vertices, PrimitiveType::TRIANGLES, &batch_states);
+
+            stats.draw_calls += 1;
+            start = end;
+        }
+
+        stats.saved_draw_calls = stats.quads - stats.draw_calls;
+        self.stats = stats;
+        self.clear();
+    }
+
+    /// Stats of the last flush
+    pub fn stats(&self) -> BatchStats {
+        self.stats
+    }
+}
@@ -0,0 +1,5 @@
+pub mod loading_state;
+pub mod dialogs;
+
+pub use loading_state::*;
+pub use dialogs::*;
\ No newline at end of file
@@ -0,0 +1,295 @@
+use crate::sfml_export::*;
+use crate::core::state_machine::{State, StateData, Transition, EventResponse};
+use crate::core::assets_manager::{FontAsset, fallback_font};
+use crate::core::localization::Localization;
+use crate::gui::{Button, DynamicText, UiManager, Widget};
+use super::loading_state::draw_centered_text;
+
+/// Sent to `State::on_message` of the state below a dialog when a button is chosen
+#[derive(Clone, Debug)]
+pub struct DialogChoice {
+    /// Id given with `DialogState::with_id` to tell the dialogs apart
+    pub dialog: String,
+    pub index: usize,
+    pub label: String
+}
+
+impl DialogChoice {
+    /// For confirm dialogs, Yes is the first button
+    pub fn is_confirmed(&self) -> bool {
+        self.index == 0
+    }
+}
+
+const BUTTON_HEIGHT: f32 = 44.0;
+const MARGIN: f32 = 20.0;
+
+/// Modal state pushed over the current one with `Transition::Add`
+///
+/// The buttons are chosen with the mouse or the keyboard (arrows and Enter),
+/// Escape chooses the cancel button if there is one. The dialog then removes itself
+/// and sends a `DialogChoice` to the state below it.
+pub struct DialogState {
+    id: String,
+    title: String,
+    text: String,
+    buttons: Vec<String>,
+    /// Localization keys of the built-in title and labels, the English text is kept without them
+    title_key: Option<&'static str>,
+    button_keys: Vec<Option<&'static str>>,
+    vertical: bool,
+    cancel_button: Option<usize>,
+
+    font: Option<FontAsset>,
+    overlay_color: Color,
+    panel_color: Color,
+    text_color: Color,
+    button_color: Color,
+    selected_color: Color,
+
+    ui_manager: UiManager<usize>,
+    message: DynamicText,
+    panel: FloatRect,
+    selected: usize
+}
+
+impl DialogState {
+    fn new(title: &str, text: &str, buttons: Vec<String>, vertical: bool, cancel_button: Option<usize>) -> Self {
+        assert!(!buttons.is_empty(), "A dialog needs at least one button");
+        Self {
+            id: String::new(),
+            title: title.to_string(),
+            text: text.to_string(),
+            button_keys: vec![None; buttons.len()],
+            buttons,
+            title_key: None,
+            vertical,
+            cancel_button,
+
+            font: None,
+            overlay_color: Color::rgba(0, 0, 0, 150),
+            panel_color: Color::rgb(40, 40, 40),
+            text_color: Color::WHITE,
+            button_color: Color::rgb(70, 70, 70),
+            selected_color: Color::rgb(110, 110, 160),
+
+            ui_manager: UiManager::new(),
+            message: DynamicText::new(),
+            panel: FloatRect::default(),
+            selected: 0
+        }
+    }
+
+    /// Message with a row of buttons, Escape chooses the last one
+    pub fn message_box(title: &str, text: &str, buttons: &[&str]) -> Self {
+        let buttons = buttons.iter().map(|button| button.to_string()).collect::<Vec<_>>();
+        let cancel_button = buttons.len().checked_sub(1);
+        Self::new(title, text, buttons, false, cancel_button)
+    }
+
+    /// Yes / No question, Escape answers No
+    ///
+    /// The labels are looked up with the `dialog.yes` and `dialog.no` keys
+    pub fn confirm(title: &str, text: &str) -> Self {
+        let mut dialog = Self::message_box(title, text, &["Yes", "No"]);
+        dialog.button_keys = vec![Some("dialog.yes"), Some("dialog.no")];
+        dialog
+    }
+
+    /// Column of items, Escape chooses the first one which is usually Resume
+    ///
+    /// The title is looked up with the `dialog.pause` key
+    pub fn pause_menu(items: &[&str]) -> Self {
+        let items = items.iter().map(|item| item.to_string()).collect();
+        let mut dialog = Self::new("Pause", "", items, true, Some(0));
+        dialog.title_key = Some("dialog.pause");
+        dialog
+    }
+
+    pub fn with_id(mut self, id: &str) -> Self {
+        self.id = id.to_string();
+        self
+    }
+
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.title = title.to_string();
+        self.title_key = None;
+        self
+    }
+
+    /// Button chosen with Escape, None to ignore Escape
+    pub fn with_cancel_button(mut self, cancel_button: Option<usize>) -> Self {
+        self.cancel_button = cancel_button;
+        self
+    }
+
+    /// The built-in font is used by default
+    pub fn with_font(mut self, font: FontAsset) -> Self {
+        self.font = Some(font);
+        self
+    }
+
+    pub fn with_colors(mut self, panel_color: Color, text_color: Color) -> Self {
+        self.panel_color = panel_color;
+        self.text_color = text_color;
+        self
+    }
+
+    pub fn with_button_colors(mut self, button_color: Color, selected_color: Color) -> Self {
+        self.button_color = button_color;
+        self.selected_color = selected_color;
+        self
+    }
+
+    /// Color drawn over the states below the dialog
+    pub fn with_overlay_color(mut self, overlay_color: Color) -> Self {
+        self.overlay_color = overlay_color;
+        self
+    }
+
+    fn localize(&mut self, localization: &Localization) {
+        let translate = |key: Option<&str>, text: &mut String| {
+            if let Some(key) = key.filter(|key| localization.has_key(key)) {
+                *text = localization.tr(key);
+            }
+        };
+        translate(self.title_key, &mut self.title);
+        for (key, label) in self.button_keys.iter().zip(self.buttons.iter_mut()) {
+            translate(*key, label);
+        }
+    }
+
+    fn layout(&mut self, target_size: Vector2f) {
+        let font = self.font.as_ref().unwrap();
+        let count = self.buttons.len() as f32;
+        let panel_width = if self.vertical { 320.0 } else { 480.0 };
+
+        let mut text_height = 0.0;
+        if !self.text.is_empty() {
+            self.message.set_font(font);
+            self.message.set_char_size(20);
+            self.message.set_color(self.text_color);
+            self.message.set_size((panel_width - 2.0 * MARGIN, 0.0));
+            self.message.set_string(&self.text);
+            text_height = self.message.global_bounds().height + MARGIN;
+        }
+
+        let buttons_height = if self.vertical {
+            count * BUTTON_HEIGHT + (count - 1.0) * MARGIN / 2.0
+        }
+        else {
+            BUTTON_HEIGHT
+        };
+
+        let panel_size = Vector2f::new(panel_width, 3.0 * MARGIN + 40.0 + text_height + buttons_height);
+        let panel_position = (target_size - panel_size) / 2.0;
+        self.panel = FloatRect::from_vecs(panel_position, panel_size);
+        self.message.set_position(panel_position + Vector2f::new(MARGIN, 2.0 * MARGIN + 40.0));
+
+        let buttons_top = panel_position.y + 2.0 * MARGIN + 40.0 + text_height;
+        for (i, label) in self.buttons.iter().enumerate() {
+            let (position, size) = if self.vertical {
+                (
+                    Vector2f::new(panel_position.x + MARGIN, buttons_top + i as f32 * (BUTTON_HEIGHT + MARGIN / 2.0)),
+                    Vector2f::new(panel_width - 2.0 * MARGIN, BUTTON_HEIGHT)
+                )
+            }
+            else {
+                let width = (panel_width - MARGIN * (count + 1.0)) / count;
+                (
+                    Vector2f::new(panel_position.x + MARGIN + i as f32 * (width + MARGIN), buttons_top),
+                    Vector2f::new(width, BUTTON_HEIGHT)
+                )
+            };
+
+            let mut button = Button::with_text(label);
+            button.set_font(font);
+            button.set_char_size(20);
+            button.set_size(size);
+            button.set_position(position);
+            self.ui_manager.add_widget(i, button);
+        }
+    }
+
+    fn choose<Data>(&mut self, index: usize, state_data: &mut StateData<Data>) -> Transition<Data> {
+        state_data.tasks.send_below(DialogChoice {
+            dialog: self.id.clone(),
+            index,
+            label: self.buttons[index].clone()
+        });
+        Transition::Remove
+    }
+}
+
+impl<Data> State<Data> for DialogState {
+    fn on_init(&mut self, state_data: &mut StateData<Data>) {
+        if self.font.is_none() {
+            self.font = Some(fallback_font());
+        }
+        self.localize(&state_data.localization);
+        let target_size = Vector2f::new(state_data.render_target_size.0 as f32, state_data.render_target_size.1 as f32);
+        self.layout(target_size);
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+
+    fn handle_event(&mut self, event: Event, state_data: &mut StateData<Data>) -> EventResponse<Data> {
+        let count = self.buttons.len();
+        self.ui_manager.on_event(event);
+
+        let mut choice = None;
+        for i in 0..count {
+            let button = self.ui_manager.get_widget(&i).unwrap();
+            if button.is_clicked() {
+                choice = Some(i);
+            }
+            if button.is_hovered() {
+                self.selected = i;
+            }
+        }
+        self.ui_manager.reset();
+
+        if let Event::KeyPressed { code, .. } = event {
+            match code {
+                Key::Left | Key::Up => self.selected = (self.selected + count - 1) % count,
+                Key::Right | Key::Down | Key::Tab => self.selected = (self.selected + 1) % count,
+                Key::Enter | Key::Space => choice = Some(self.selected),
+                Key::Escape => choice = self.cancel_button,
+                _ => {}
+            }
+        }
+
+        // Modal, the events never reach the states below
+        match choice {
+            Some(index) => EventResponse::consumed(self.choose(index, state_data)),
+            None => EventResponse::consumed(Transition::None)
+        }
+    }
+
+    fn on_render(&mut self, _state_data: &mut StateData<Data>, target: &mut dyn RenderTarget) -> bool {
+        let target_size = target.size().as_other::<f32>();
+        let mut overlay = RectangleShape::with_size(target_size);
+        overlay.set_fill_color(self.overlay_color);
+        target.draw(&overlay);
+
+        let mut panel = RectangleShape::with_size(self.panel.size());
+        panel.set_position(self.panel.position());
+        panel.set_fill_color(self.panel_color);
+        target.draw(&panel);
+
+        let font = self.font.as_ref().unwrap();
+        draw_centered_text(target, font, &self.title, 28, self.text_color, self.panel.top + MARGIN);
+        if !self.text.is_empty() {
+            target.draw(&self.message);
+        }
+
+        for i in 0..self.buttons.len() {
+            let color = if i == self.selected { self.selected_color } else { self.button_color };
+            self.ui_manager.with_widget_as(&i, Button::set_color, color);
+        }
+        self.ui_manager.draw(target);
+        true
+    }
+}
@@ -0,0 +1,374 @@
+use std::any::Any;
+use std::future::Future;
+use std::pin::Pin;
+use crate::sfml_export::*;
+use crate::core::state_machine::{State, StateData, Transition, EventResponse};
+use crate::core::assets_manager::{AssetID, DefaultAssetsManager, FontAsset, fallback_font};
+use crate::gui::{ProgressBar, Widget};
+
+type AssetTypeResolver = Box<dyn Fn(&DefaultAssetsManager, &str) -> usize>;
+type TaskFactory = Box<dyn Fn() -> Pin<Box<dyn Future<Output = Result<(), String>>>>>;
+
+struct BundleEntry {
+    asset_type: AssetTypeResolver,
+    id: AssetID,
+    path: String
+}
+
+/// Assets loaded together by a `LoadingState`
+#[derive(Default)]
+pub struct AssetsBundle {
+    entries: Vec<BundleEntry>
+}
+
+impl AssetsBundle {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new()
+        }
+    }
+
+    /// Asset loaded by the group registered for the type `A`
+    pub fn with<A: 'static>(self, id: &str, path: &str) -> Self {
+        self.with_entry(Box::new(|manager, _| manager.typed_index::<A>()), id, path)
+    }
+
+    pub fn with_asset<AT: Into<usize>>(self, asset_type: AT, id: &str, path: &str) -> Self {
+        let asset_type = asset_type.into();
+        self.with_entry(Box::new(move |_, _| asset_type), id, path)
+    }
+
+    /// Asset loaded by the group registered for the file extension
+    pub fn with_by_extension(self, id: &str, path: &str) -> Self {
+        self.with_entry(Box::new(|manager, path| manager.extension_index(path)), id, path)
+    }
+
+    fn with_entry(mut self, asset_type: AssetTypeResolver, id: &str, path: &str) -> Self {
+        self.entries.push(BundleEntry {
+            asset_type,
+            id: id.to_string(),
+            path: path.to_string()
+        });
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+//////////////////////////////////////
+
+struct LoadEntry {
+    asset_type: usize,
+    id: AssetID,
+    path: String
+}
+
+struct TaskDone {
+    index: usize,
+    result: Result<(), String>
+}
+
+enum LoadingStatus {
+    Loading,
+    Failed
+}
+
+/// Loads assets bundles and runs background tasks while displaying a progress bar,
+/// then replaces itself by the next state
+///
+/// The loadings already queued in the buffered loader of the assets manager by the previous
+/// states are launched first, then the assets of the bundles.
+/// If an asset or a task fails, the errors are displayed with a retry screen:
+/// Enter retries the failed loadings, Escape moves on with the fallback assets.
+pub struct LoadingState<Data> {
+    bundles: Vec<AssetsBundle>,
+    tasks: Vec<TaskFactory>,
+    next_state: Option<Box<dyn State<Data>>>,
+    loads_per_frame: usize,
+
+    entries: Vec<LoadEntry>,
+    loading_entries: Vec<usize>,
+    loading_tasks: Vec<usize>,
+    entries_done: usize,
+    tasks_done: usize,
+    failed_entries: Vec<(usize, String)>,
+    failed_tasks: Vec<(usize, String)>,
+    errors: Vec<String>,
+    status: LoadingStatus,
+
+    title: String,
+    font: Option<FontAsset>,
+    background_color: Color,
+    text_color: Color,
+    progress_bar: Box<ProgressBar>
+}
+
+impl<Data> LoadingState<Data> {
+    pub fn new(next_state: Box<dyn State<Data>>) -> Self {
+        let mut progress_bar = ProgressBar::new();
+        progress_bar.set_size(Vector2f::new(400.0, 24.0));
+
+        Self {
+            bundles: Vec::new(),
+            tasks: Vec::new(),
+            next_state: Some(next_state),
+            loads_per_frame: 1,
+
+            entries: Vec::new(),
+            loading_entries: Vec::new(),
+            loading_tasks: Vec::new(),
+            entries_done: 0,
+            tasks_done: 0,
+            failed_entries: Vec::new(),
+            failed_tasks: Vec::new(),
+            errors: Vec::new(),
+            status: LoadingStatus::Loading,
+
+            title: "Loading".to_string(),
+            font: None,
+            background_color: Color::BLACK,
+            text_color: Color::WHITE,
+            progress_bar
+        }
+    }
+
+    pub fn with_bundle(mut self, bundle: AssetsBundle) -> Self {
+        self.bundles.push(bundle);
+        self
+    }
+
+    /// Background work counted in the progress, the factory is called again on retry
+    pub fn with_task<F: Future<Output = Result<(), String>> + 'static>(mut self, task: impl Fn() -> F + 'static) -> Self {
+        self.tasks.push(Box::new(move || Box::pin(task())));
+        self
+    }
+
+    /// Number of loadings launched each frame
+    pub fn with_loads_per_frame(mut self, loads_per_frame: usize) -> Self {
+        self.loads_per_frame = loads_per_frame.max(1);
+        self
+    }
+
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.title = title.to_string();
+        self
+    }
+
+    /// The built-in font is used by default
+    pub fn with_font(mut self, font: FontAsset) -> Self {
+        self.font = Some(font);
+        self
+    }
+
+    pub fn with_colors(mut self, background_color: Color, text_color: Color) -> Self {
+        self.background_color = background_color;
+        self.text_color = text_color;
+        self
+    }
+
+    pub fn with_bar_size(mut self, size: Vector2f) -> Self {
+        self.progress_bar.set_size(size);
+        self
+    }
+
+    pub fn with_bar_colors(mut self, background_color: Color, fill_color: Color) -> Self {
+        self.progress_bar.set_colors(background_color, fill_color);
+        self
+    }
+
+    pub fn with_bar_outline(mut self, outline_color: Color, outline_thickness: f32) -> Self {
+        self.progress_bar.set_outline(outline_color, outline_thickness);
+        self
+    }
+
+    /// Between 0 and 1
+    pub fn progress(&self) -> f32 {
+        self.progress_bar.progress()
+    }
+
+    fn start_loading(&mut self, state_data: &mut StateData<Data>, entries: Vec<usize>, tasks: Vec<usize>) {
+        self.loading_entries = entries;
+        self.loading_tasks = tasks;
+        self.entries_done = 0;
+        self.tasks_done = 0;
+
+        for &i in self.loading_tasks.iter() {
+            let task = (self.tasks[i])();
+            let spawned = state_data.tasks.spawn(async move {
+                TaskDone {
+                    index: i,
+                    result: task.await
+                }
+            });
+            if let Err(error) = spawned {
+                self.tasks_done += 1;
+                self.failed_tasks.push((i, error));
+            }
+        }
+
+        self.errors.clear();
+        self.status = LoadingStatus::Loading;
+        self.progress_bar.set_progress(0.0);
+    }
+
+    fn retry(&mut self, state_data: &mut StateData<Data>) {
+        let entries = self.failed_entries.drain(..).map(|(i, _)| i).collect::<Vec<_>>();
+        for &i in entries.iter() {
+            let entry = &self.entries[i];
+            state_data.assets_manager.unload_asset(entry.asset_type, entry.id.clone());
+        }
+
+        let tasks = self.failed_tasks.drain(..).map(|(i, _)| i).collect();
+        self.start_loading(state_data, entries, tasks);
+    }
+
+    /// Loads the entry, its failure is the error added by this loading
+    fn load_entry(&mut self, index: usize, assets_manager: &mut DefaultAssetsManager) {
+        let errors_count = assets_manager.load_errors().len();
+        let entry = &self.entries[index];
+        assets_manager.load_asset(entry.asset_type, entry.id.clone(), &entry.path);
+
+        if let Some(error) = assets_manager.load_errors().get(errors_count) {
+            self.failed_entries.push((index, error.to_string()));
+        }
+    }
+
+    fn update_progress(&mut self) {
+        let total = self.loading_entries.len() + self.loading_tasks.len();
+        if total == 0 {
+            self.progress_bar.set_progress(1.0);
+        }
+        else {
+            self.progress_bar.set_progress((self.entries_done + self.tasks_done) as f32 / total as f32);
+        }
+    }
+
+    fn collect_errors(&mut self) {
+        self.errors = self.failed_entries.iter()
+            .chain(self.failed_tasks.iter())
+            .map(|(_, error)| error.clone())
+            .collect();
+    }
+
+    fn next_transition(&mut self) -> Transition<Data> {
+        match self.next_state.take() {
+            Some(next_state) => Transition::Replace(next_state),
+            None => Transition::Remove
+        }
+    }
+}
+
+pub(crate) fn draw_centered_text(target: &mut dyn RenderTarget, font: &Font, string: &str, char_size: u32, color: Color, y: f32) {
+    let mut text = Text::new(string, font, char_size);
+    text.set_fill_color(color);
+    let bounds = text.local_bounds();
+    text.set_position(((target.size().x as f32 - bounds.width) / 2.0 - bounds.left, y));
+    target.draw(&text);
+}
+
+impl<Data> State<Data> for LoadingState<Data> {
+    fn on_init(&mut self, state_data: &mut StateData<Data>) {
+        if self.font.is_none() {
+            self.font = Some(fallback_font());
+        }
+
+        for bundle in std::mem::take(&mut self.bundles) {
+            for entry in bundle.entries {
+                self.entries.push(LoadEntry {
+                    asset_type: (entry.asset_type)(&state_data.assets_manager, &entry.path),
+                    id: entry.id,
+                    path: entry.path
+                });
+            }
+        }
+
+        let entries = (0..self.entries.len()).collect();
+        let tasks = (0..self.tasks.len()).collect();
+        self.start_loading(state_data, entries, tasks);
+    }
+
+    fn on_update(&mut self, state_data: &mut StateData<Data>) -> Transition<Data> {
+        if let LoadingStatus::Failed = self.status {
+            return Transition::None;
+        }
+
+        for _ in 0..self.loads_per_frame {
+            if state_data.assets_manager.is_loading() {
+                state_data.assets_manager.launch_loadings();
+            }
+            else if let Some(&index) = self.loading_entries.get(self.entries_done) {
+                self.load_entry(index, &mut state_data.assets_manager);
+                self.entries_done += 1;
+            }
+        }
+        self.update_progress();
+
+        if state_data.assets_manager.is_loading()
+            || self.entries_done < self.loading_entries.len()
+            || self.tasks_done < self.loading_tasks.len() {
+            return Transition::None;
+        }
+
+        self.collect_errors();
+        if self.errors.is_empty() {
+            self.next_transition()
+        }
+        else {
+            self.status = LoadingStatus::Failed;
+            Transition::None
+        }
+    }
+
+    fn on_message(&mut self, message: Box<dyn Any>, _state_data: &mut StateData<Data>) -> Transition<Data> {
+        if let Ok(task_done) = message.downcast::<TaskDone>() {
+            self.tasks_done += 1;
+            if let Err(error) = task_done.result {
+                self.failed_tasks.push((task_done.index, error));
+            }
+        }
+        Transition::None
+    }
+
+    fn handle_event(&mut self, event: Event, state_data: &mut StateData<Data>) -> EventResponse<Data> {
+        if let (LoadingStatus::Failed, Event::KeyPressed { code, .. }) = (&self.status, event) {
+            match code {
+                Key::Enter | Key::R => self.retry(state_data),
+                Key::Escape => return EventResponse::consumed(self.next_transition()),
+                _ => {}
+            }
+        }
+        EventResponse::consumed(Transition::None)
+    }
+
+    fn on_render(&mut self, _state_data: &mut StateData<Data>, target: &mut dyn RenderTarget) -> bool {
+        target.clear(self.background_color);
+        let font = self.font.as_ref().unwrap();
+        let target_size = target.size().as_other::<f32>();
+
+        match self.status {
+            LoadingStatus::Loading => {
+                let bar_size = self.progress_bar.size();
+                self.progress_bar.set_position((target_size - bar_size) / 2.0);
+                draw_centered_text(target, font, &self.title, 30, self.text_color, target_size.y / 2.0 - bar_size.y - 50.0);
+                self.progress_bar.draw(target);
+            },
+            LoadingStatus::Failed => {
+                let mut y = target_size.y / 4.0;
+                draw_centered_text(target, font, "Loading failed", 30, self.text_color, y);
+                y += 60.0;
+                for error in self.errors.iter().take(8) {
+                    draw_centered_text(target, font, error, 14, self.text_color, y);
+                    y += 24.0;
+                }
+                draw_centered_text(target, font, "Enter: retry   Escape: continue", 20, self.text_color, y + 40.0);
+            }
+        }
+        true
+    }
+}
@@ -1,23 +1,113 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use sfmx::prelude::*;
-use crate::game_over::GameOverState;
+use crate::game_over::{Background, GameOverState};
 use crate::grid::{DrawableGrid, GameStatus};
 use crate::GameData;
 use crate::player::Player;
 use crate::mygui::{Button, ButtonsGroup};
 
-enum GamePlayState {
-    Playing,
-    GameOverAnimation,
-    GotoGameOverState
-}
-
-pub struct GameState {
-    grid: Option<DrawableGrid>,
+struct Board {
+    grid: DrawableGrid,
     players: [Box<dyn Player>; 2],
     current_player_index: usize,
-    game_play_state: GamePlayState,
+    celebration: Option<ParticleEmitter>
+}
+
+type BoardRef = Rc<RefCell<Board>>;
+
+struct PlayingState {
+    board: BoardRef
+}
+
+impl State<GameData> for PlayingState {
+    fn on_event(&mut self, event: Event, state_data: &mut StateData<GameData>) -> Transition<GameData> {
+        let mut board = self.board.borrow_mut();
+        let current_player_index = board.current_player_index;
+        board.players[current_player_index].on_event(event);
+        Transition::None
+    }
+
+    fn on_update(&mut self, state_data: &mut StateData<GameData>) -> Transition<GameData> {
+        let board = &mut *self.board.borrow_mut();
+        let current_player = &mut board.players[board.current_player_index];
+        if !current_player.play(&mut board.grid) {
+            return Transition::None;
+        }
+
+        current_player.forbid();
+        let game_status = board.grid.get_winner();
+        if game_status == GameStatus::NotFinished {
+            board.current_player_index = (board.current_player_index + 1) % 2;
+            board.players[board.current_player_index].allow();
+            return Transition::None;
+        }
+
+        for player in board.players.iter_mut() {
+            (*player).forbid();
+        }
+
+        if game_status == GameStatus::Draw {
+            Transition::Parent(Box::new(Transition::Replace(Box::new(GameOverState::new(game_status, board_background(&self.board))))))
+        }
+        else {
+            board.grid.init_winning_line();
+            Transition::Replace(Box::new(GameOverAnimationState::new(game_status, self.board.clone())))
+        }
+    }
+}
+
+fn board_background(board: &BoardRef) -> Background {
+    let board = board.clone();
+    Box::new(move |target| board.borrow_mut().grid.draw(target))
+}
+
+struct GameOverAnimationState {
     game_status: GameStatus,
-    game_over_timer: Timer,
+    timer: Timer,
+    board: BoardRef
+}
+
+impl GameOverAnimationState {
+    fn new(game_status: GameStatus, board: BoardRef) -> Self {
+        Self {
+            game_status,
+            timer: Timer::new(),
+            board
+        }
+    }
+}
+
+impl State<GameData> for GameOverAnimationState {
+    fn on_init(&mut self, state_data: &mut StateData<GameData>) {
+        match ParticleConfig::load(state_data.assets_manager.file_system(), "assets/particles/celebration.toml") {
+            Ok(config) => {
+                let mut board = self.board.borrow_mut();
+                let mut celebration = ParticleEmitter::new(config).with_position(board.grid.winning_cells_center());
+                celebration.start();
+                board.celebration = Some(celebration);
+            },
+            Err(error) => eprintln!("{}", error)
+        }
+    }
+
+    fn on_update(&mut self, state_data: &mut StateData<GameData>) -> Transition<GameData> {
+        if let Some(celebration) = self.board.borrow_mut().celebration.as_mut() {
+            celebration.update(state_data.delta_time);
+        }
+
+        if self.timer.elapsed().as_secs_f32() >= 1.25 {
+            return Transition::Parent(Box::new(Transition::Replace(Box::new(GameOverState::new(self.game_status, board_background(&self.board))))));
+        }
+        Transition::None
+    }
+}
+
+pub struct GameState {
+    board: Option<BoardRef>,
+    players: Option<[Box<dyn Player>; 2]>,
+    play_machine: StateMachine<GameData>,
     grid_data: GridData,
     buttons: ButtonsGroup
 
@@ -32,12 +122,9 @@ pub struct GridData {
 impl GameState {
     pub fn new(grid_data: GridData, players: [Box<dyn Player>; 2]) -> Self {
         GameState {
-            grid: None,
-            players,
-            current_player_index: 0,
-            game_play_state: GamePlayState::Playing,
-            game_status: GameStatus::NotFinished,
-            game_over_timer: Timer::new(),
+            board: None,
+            players: Some(players),
+            play_machine: StateMachine::new(),
             grid_data,
             buttons: ButtonsGroup::new()
         }
@@ -47,92 +134,70 @@ impl GameState {
 impl State<GameData> for GameState {
 
     fn on_init(&mut self, state_data: &mut StateData<GameData>) {
-        let x_texture = state_data.assets_manager.get_asset(AssetType::Texture, "X".to_string()).unwrap();
-        let o_texture = state_data.assets_manager.get_asset(AssetType::Texture, "O".to_string()).unwrap();
-        let white_texture = state_data.assets_manager.get_asset(AssetType::Texture, "white".to_string()).unwrap();
-        let quit_btn_texture = state_data.assets_manager.get_asset(AssetType::Texture, "quit_btn".to_string()).unwrap();
+        let atlas: &TextureAtlas = state_data.assets_manager.get_asset(AssetType::Atlas, "sprites".to_string()).unwrap();
 
         let mut dims = Vector2::from(state_data.data.screen_size).as_other();
         dims.y = dims.y * 0.9;
-        let mut grid = DrawableGrid::new([white_texture, x_texture, o_texture],
+        let mut grid = DrawableGrid::new(["white", "X", "O"].map(|name| atlas.sprite(name).unwrap()),
             10.0, 
             self.grid_data.grid_size, 
             self.grid_data.sym_occs_win, 
             dims
         );
         grid.set_position(Vector2f::new(0.0, state_data.data.screen_size.1 as f32 - dims.y));
-        self.grid = Some(grid);
 
-        self.players[0].allow();
+        let mut players = self.players.take().unwrap();
+        players[0].allow();
+
         let button_height = state_data.data.screen_size.1 as f32 - dims.y;
-        let quit_btn = Button::new(quit_btn_texture, (button_height * 2.0, button_height));
+        let quit_btn = Button::new(atlas.sprite("quit_btn").unwrap(), (button_height * 2.0, button_height));
         self.buttons.add_button("quit_btn", quit_btn);
 
+        let board = Rc::new(RefCell::new(Board {
+            grid,
+            players,
+            current_player_index: 0,
+            celebration: None
+        }));
+        self.board = Some(board.clone());
+        self.play_machine = StateMachine::with_initial_state(Box::new(PlayingState { board }), state_data);
+    }
+
+    fn on_end(&mut self, state_data: &mut StateData<GameData>) {
+        self.play_machine.terminate(state_data);
     }
 
     fn on_event(&mut self, event: Event, state_data: &mut StateData<GameData>) -> Transition<GameData> {
         self.buttons.on_event(event);
-        self.players[self.current_player_index].on_event(event);
+        self.play_machine.on_event(event, state_data);
         Transition::None
     }
 
     fn on_update(&mut self, state_data: &mut StateData<GameData>) -> Transition<GameData> {
-
-        match self.game_play_state {
-            GamePlayState::Playing => {
-                let grid = self.grid.as_mut().unwrap();
-                let current_player = &mut self.players[self.current_player_index];
-                if current_player.play(grid) {
-                    current_player.forbid();
-                    self.game_status = grid.get_winner();
-                    if self.game_status != GameStatus::NotFinished {
-                        if self.game_status == GameStatus::Draw {
-                            self.game_play_state = GamePlayState::GotoGameOverState;
-                        }
-                        else {
-                            grid.init_winning_line();
-                            self.game_over_timer.restart();
-                            self.game_play_state = GamePlayState::GameOverAnimation;
-                        }
-                        for player in self.players.iter_mut() {
-                            (*player).forbid();
-                        }
-                        return Transition::None;
-                    }
-                    else {
-                        self.current_player_index = (self.current_player_index + 1) % 2;
-                        self.players[self.current_player_index].allow();
-                    }
-                }
-
-                if self.buttons.get_button("quit_btn").is_clicked() {
-                    return Transition::Remove;
-                }
-
-                self.buttons.reset();
-
-                Transition::None
-            },
-            GamePlayState::GameOverAnimation => {
-                if self.game_over_timer.elapsed().as_secs_f32() >= 1.25 {
-                    self.game_play_state = GamePlayState::GotoGameOverState;
-                }
-                Transition::None
-            },
-            GamePlayState::GotoGameOverState => {
-                return Transition::Replace(Box::new(GameOverState::new(self.game_status)))
-            }
+        if self.buttons.get_button("quit_btn").is_clicked() {
+            return Transition::Remove;
         }
+        self.buttons.reset();
+
+        self.play_machine.on_update(state_data)
     }
 
     fn on_render(&mut self, state_data: &mut StateData<GameData>, window: &mut dyn RenderTarget) -> bool {
-        if !self.grid.as_ref().unwrap().can_draw_sprites() {
+        let board = &mut *self.board.as_ref().unwrap().borrow_mut();
+        let celebrating = board.celebration.as_ref().is_some_and(|celebration| celebration.is_active());
+        if !board.grid.can_draw_sprites() && !celebrating {
             return false;
         }
         window.clear(state_data.data.clear_color);
-        self.grid.as_mut().unwrap().draw(window);
-        self.buttons.draw(window);
+
+        let mut queue = RenderQueue::new();
+        queue.submit_fn(WORLD_LAYER, 0.0, |target| board.grid.draw(target));
+        if let Some(celebration) = board.celebration.as_ref() {
+            queue.submit(WORLD_LAYER, 1.0, celebration);
+        }
+        queue.submit_fn(UI_LAYER, 0.0, |target| self.buttons.draw(target));
+        queue.flush(window);
 
         return true;
     }
-}
\ No newline at end of file
+}
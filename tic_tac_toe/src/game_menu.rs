@@ -31,7 +31,7 @@ impl GameMenuState {
 impl State<GameData> for GameMenuState {
 
     fn on_init(&mut self, state_data: &mut StateData<GameData>) {
-        let arrow_texture = state_data.assets_manager.get_asset(AssetType::Texture, "arrow".to_string()).unwrap();
+        let atlas: &TextureAtlas = state_data.assets_manager.get_asset(AssetType::Atlas, "sprites".to_string()).unwrap();
         let win_size = Vector2::from(state_data.data.screen_size).as_other::<f32>();
         let window_eigth = win_size.x / 8.0;
         let buttons_height = 150.0;
@@ -39,7 +39,7 @@ impl State<GameData> for GameMenuState {
         let buttons_size = Vector2f::new(100.0, 100.0);
         
         let mut arrow_right_btn1 = Button::new(
-            arrow_texture,
+            atlas.sprite("arrow").unwrap(),
             buttons_size
         );
         arrow_right_btn1.set_position((
@@ -50,7 +50,7 @@ impl State<GameData> for GameMenuState {
         self.buttons_group.add_button("right_arrow1", arrow_right_btn1);
 
         let mut arrow_left_btn1 = Button::new(
-            arrow_texture,
+            atlas.sprite("arrow").unwrap(),
             buttons_size
         );
         arrow_left_btn1.set_rotation(180.0);
@@ -63,7 +63,7 @@ impl State<GameData> for GameMenuState {
 
         //
         let mut arrow_right_btn2 = Button::new(
-            arrow_texture,
+            atlas.sprite("arrow").unwrap(),
             buttons_size
         );
 
@@ -75,7 +75,7 @@ impl State<GameData> for GameMenuState {
         self.buttons_group.add_button("right_arrow2", arrow_right_btn2);
 
         let mut arrow_left_btn2 = Button::new(
-            arrow_texture,
+            atlas.sprite("arrow").unwrap(),
             buttons_size
         );
         arrow_left_btn2.set_rotation(180.0);
@@ -86,9 +86,8 @@ impl State<GameData> for GameMenuState {
         self.buttons_group.add_button("left_arrow2", arrow_left_btn2);
 
 
-        let start_btn_texture = state_data.assets_manager.get_asset(AssetType::Texture, "start_btn".to_string()).unwrap();
         let start_btn_size = Vector2f::from((200.0, 100.0));
-        let mut start_btn = Button::new(start_btn_texture, start_btn_size);
+        let mut start_btn = Button::new(atlas.sprite("start_btn").unwrap(), start_btn_size);
 
         start_btn.set_position((
             (win_size.x - start_btn_size.x) / 2.0,
@@ -160,7 +159,7 @@ impl State<GameData> for GameMenuState {
 
     fn on_render(&mut self, state_data: &mut StateData<GameData>, window: &mut dyn RenderTarget) -> bool{
         window.clear(state_data.data.clear_color);
-        let font: &SfBox<Font> = state_data.assets_manager.get_asset(AssetType::Font, "font".to_string()).unwrap();
+        let font: &FontAsset = state_data.assets_manager.get_asset(AssetType::Font, "font".to_string()).unwrap();
         let mut text = Text::default();
         text.set_font(font);
         text.set_fill_color(Color::BLACK);
@@ -205,12 +204,12 @@ impl State<GameData> for GameMenuState {
         }
 
         let texts = [
-            "Grid\ndimensions",
-            "Matching\npattern"
+            state_data.localization.tr("game_menu.grid_dimensions"),
+            state_data.localization.tr("game_menu.matching_pattern")
         ];
         let offset = 50.0;
         for (i, text_string) in texts.iter().enumerate() {
-            text.set_string(*text_string);
+            text.set_string(text_string);
             text.set_character_size(30);
             
             let text_pos_x = (2.0 * i as f32 + 1.0) * window_quarter - text.global_bounds().width / 2.0;
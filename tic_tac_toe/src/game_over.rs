@@ -3,18 +3,23 @@ use sfmx::prelude::*;
 use crate::{GameData, grid::GameStatus};
 use crate::mygui;
 
+pub type Background = Box<dyn Fn(&mut dyn RenderTarget)>;
+
 pub struct GameOverState {
     game_status: GameStatus,
-    text: String,
-    buttons: mygui::ButtonsGroup
+    buttons: mygui::ButtonsGroup,
+    /// Last board of the game, drawn blurred behind the result
+    background: Background,
+    background_blur: Option<PostProcess>
 }
 
 impl GameOverState {
-    pub fn new(game_status: GameStatus) -> GameOverState {
+    pub fn new(game_status: GameStatus, background: Background) -> GameOverState {
         Self {
             game_status,
-            text: String::from("Game Over\n\n"),
-            buttons: mygui::ButtonsGroup::new()
+            buttons: mygui::ButtonsGroup::new(),
+            background,
+            background_blur: None
         }
     }
 }
@@ -22,31 +27,32 @@ impl GameOverState {
 impl State<GameData> for GameOverState {
     fn on_init(&mut self, state_data: &mut StateData<GameData>) {
 
-        match self.game_status {
-            GameStatus::Draw => self.text.push_str("Draw"),
-            GameStatus::Winner(s) => self.text.push_str(&format!("Winner is {:?}", s)),
-            _ => {}
-        }
-
-        let restart_btn_texture = state_data.assets_manager.get_asset(AssetType::Texture, "restart_btn".to_string()).unwrap();
-        let quit_btn_texture = state_data.assets_manager.get_asset(AssetType::Texture, "quit_btn".to_string()).unwrap();
+        let atlas: &TextureAtlas = state_data.assets_manager.get_asset(AssetType::Atlas, "sprites".to_string()).unwrap();
 
         let buttons_size = Vector2f::new(200.0, 100.0);
         let win_size = Vector2::from(state_data.data.screen_size).as_other::<f32>();
 
-        let mut restart_btn  = mygui::Button::new(restart_btn_texture, buttons_size);
+        let mut restart_btn  = mygui::Button::new(atlas.sprite("restart_btn").unwrap(), buttons_size);
         restart_btn.set_position((
             (win_size.x - buttons_size.x) / 2.0,
             win_size.y * 2.0 / 3.0 - buttons_size.y + 30.0
         ));
         self.buttons.add_button("restart", restart_btn);
 
-        let mut qui_btn = mygui::Button::new(quit_btn_texture, buttons_size);
+        let mut qui_btn = mygui::Button::new(atlas.sprite("quit_btn").unwrap(), buttons_size);
         qui_btn.set_position((
             (win_size.x - buttons_size.x) / 2.0,
             win_size.y * 2.0 / 3.0 + 50.0
         ));
         self.buttons.add_button("quit", qui_btn);
+
+        match PostPass::blur() {
+            Ok(mut blur) => {
+                blur.set_float("radius", 3.0);
+                self.background_blur = Some(PostProcess::new().with_pass(blur));
+            },
+            Err(error) => eprintln!("{}", error)
+        }
     }
 
 
@@ -69,8 +75,25 @@ impl State<GameData> for GameOverState {
 
     fn on_render(&mut self, state_data: &mut StateData<GameData>, window: &mut dyn RenderTarget) -> bool {
         window.clear(state_data.data.clear_color);
-        let font: &SfBox<Font> = state_data.assets_manager.get_asset(AssetType::Font, "font".to_string()).unwrap();
-        let mut text_ui = Text::new(&self.text, font, 50);
+        if let Some(blur) = &mut self.background_blur {
+            blur.render(window, |target| (self.background)(target));
+
+            let clear_color = state_data.data.clear_color;
+            let mut veil = RectangleShape::with_size(window.size().as_other());
+            veil.set_fill_color(Color::rgba(clear_color.r, clear_color.g, clear_color.b, 160));
+            window.draw(&veil);
+        }
+
+        let localization = &state_data.localization;
+        let result = match self.game_status {
+            GameStatus::Draw => localization.tr("draw"),
+            GameStatus::Winner(s) => localization.tr_with("winner", &[("player", &format!("{:?}", s))]),
+            _ => String::new()
+        };
+        let text = format!("{}\n\n{}", localization.tr("game_over"), result);
+
+        let font: &FontAsset = state_data.assets_manager.get_asset(AssetType::Font, "font".to_string()).unwrap();
+        let mut text_ui = Text::new(&text, font, 50);
         text_ui.set_fill_color(Color::BLACK);
         text_ui.set_position((
             (window.size().x as f32 - text_ui.global_bounds().width as f32) / 2.0,
@@ -169,32 +169,10 @@ impl Grid {
     }
 }
 
-#[derive(Default, Debug)]
-struct Line {
-    start_point: Vector2f,
-    length: f32,
-    rotation_from_x_axis: f32,
-}
-
-impl Line {
-
-    fn new(start_point: Vector2f, end_point: Vector2f) -> Self  {
-        let diff_vector = end_point - start_point;
-        let length = diff_vector.length_sq().sqrt();
-        let line_dir = diff_vector / length;
-
-        let e1 = Vector2f::new(1.0, 0.0);
-        let rotation_angle = e1.dot(line_dir).acos().to_degrees();
-        Self {
-            start_point,
-            length,
-            rotation_from_x_axis: rotation_angle
-        }
-    }
-}
 pub struct DrawableGrid {
     grid: Grid,
     grid_sprites: [RcSprite; 3],
+    cells_color: Color,
     cells_offset: f32,
     cell_size: Vector2f,
     should_draw_sprites: bool,
@@ -202,33 +180,27 @@ pub struct DrawableGrid {
     position: Vector2f,
 
     winnign_patters: Vec<(usize, usize)>,
-    game_over_line: Line,
-    draw_line: bool
+    /// End points relative to the grid position
+    winning_line: Option<(Vector2f, Vector2f)>
 }
 
 impl DrawableGrid {
-    pub fn new(grid_textures: [&RcTexture; Symbol::Count as usize],
+    pub fn new(grid_sprites: [RcSprite; Symbol::Count as usize],
         cells_offset: f32,
         grid_size: usize,
         sym_occs_win: u32,
         dimensions: impl Into<Vector2f>) -> DrawableGrid {
 
-        let mut grid_sprites: [RcSprite; 3] = Default::default();
-
-        for i in 0..Symbol::Count as usize {
-            grid_sprites[i].set_texture(grid_textures[i], true);
-            grid_sprites[i].set_color(Color::rgb(92,192,192))
-        }
         Self {
             grid: Grid::new(grid_size, sym_occs_win),
             grid_sprites,
+            cells_color: Color::rgb(92,192,192),
             cells_offset,
             cell_size: Vector2f::default(),
             should_draw_sprites: true,
             dimensions: dimensions.into(),
             winnign_patters: Vec::new(),
-            game_over_line: Default::default(),
-            draw_line: false,
+            winning_line: None,
             position: Default::default()
         }
     }
@@ -267,35 +239,30 @@ impl DrawableGrid {
         self.position = pos;
     }
 
-    pub fn init_winning_line(&mut self) {
-        let index1 = self.winnign_patters[0];
-        let index2 = self.winnign_patters.last().unwrap();
-
-        let mut offset = 0.0;
-        if index1.0 > index2.0 {
-            offset = self.cell_size.x;
-        }
-
-        let p1 = Vector2f::from((
-            index1.0 as f32  * (self.cell_size.x + self.cells_offset) + self.cells_offset / 2.0 + offset,
-            index1.1 as f32  * (self.cell_size.y + self.cells_offset) + self.cells_offset / 2.0
-        ));
-
-        let p2 = Vector2f::from((
-            index2.0 as f32  * (self.cell_size.x + self.cells_offset) + self.cells_offset / 2.0 - offset,
-            index2.1 as f32  * (self.cell_size.y + self.cells_offset) + self.cells_offset / 2.0
-        ));
-
-        let factor = (
-            (p1.x - p2.x).abs().clamp(0.0, 1.0),
-            (p1.y - p2.y).abs().clamp(0.0, 1.0),
-        );
+    /// Center of the cell relative to the grid position
+    fn cell_center(&self, (x, y): (usize, usize)) -> Vector2f {
+        let half_offset = self.cells_offset / 2.0;
+        Vector2f::new(
+            x as f32 * (self.cell_size.x + self.cells_offset) + half_offset + self.cell_size.x / 2.0,
+            y as f32 * (self.cell_size.y + self.cells_offset) + half_offset + self.cell_size.y / 2.0
+        )
+    }
 
-        let p2 = p2 + Vector2f::from((factor.0 * self.cell_size.x, factor.1 * self.cell_size.y));
+    /// Line through the winning cells, going a bit past the first and last ones
+    pub fn init_winning_line(&mut self) {
+        let first = self.cell_center(self.winnign_patters[0]);
+        let last = self.cell_center(*self.winnign_patters.last().unwrap());
 
-        self.game_over_line = Line::new(p1, p2);
+        let direction = last - first;
+        let direction = direction / direction.length_sq().sqrt().max(f32::EPSILON);
+        let overshoot = Vector2f::new(direction.x * self.cell_size.x, direction.y * self.cell_size.y) * 0.4;
+        self.winning_line = Some((first - overshoot, last + overshoot));
+    }
 
-        self.draw_line = true;
+    /// Center of the winning cells, in target coordinates
+    pub fn winning_cells_center(&self) -> Vector2f {
+        let sum = self.winnign_patters.iter().fold(Vector2f::default(), |sum, cell| sum + self.cell_center(*cell));
+        sum / self.winnign_patters.len().max(1) as f32 + self.position
     }
 
     pub fn on_mouse_click(&mut self, x: f32, y: f32, symbol: Symbol) -> bool {
@@ -324,47 +291,30 @@ impl DrawableGrid {
 
         let cell_size = self.dimensions / self.grid.grid_size as f32 - Vector2::new(self.cells_offset , self.cells_offset );
         self.cell_size = cell_size;
-        for sprite in &mut self.grid_sprites {
-            let texture_size = sprite.texture().unwrap().size().as_other::<f32>();
-            sprite.set_scale((
-                cell_size.x / texture_size.x,
-                cell_size.y / texture_size.y
-            ));
-        }
 
+        // One draw call per symbol instead of one per cell
+        let mut batch = SpriteBatch::new();
         for x in 0..self.grid.grid_size {
             for y in 0..self.grid.grid_size {
                 let sprite = &mut self.grid_sprites[self.grid.get_symbol(x, y) as usize];
+                let texture_size = sprite.texture_rect().size().as_other::<f32>();
                 let sprite_pos = Vector2f::new(
                     x as f32 * (cell_size.x + self.cells_offset) + half_offset,
                     y as f32 * (cell_size.y + self.cells_offset) + half_offset
-                );
-                sprite.set_position(sprite_pos + self.position);
-
-                target.draw(sprite);
+                ) + self.position;
 
+                sprite.set_position(sprite_pos);
+                sprite.set_scale((cell_size.x / texture_size.x, cell_size.y / texture_size.y));
+                sprite.set_color(self.cells_color);
+                batch.draw_rc_sprite(sprite);
             }
         }
+        batch.flush(target, &RenderStates::DEFAULT);
 
-        if self.draw_line {
-            let p = self.game_over_line.start_point;
-            let length = self.game_over_line.length;
-            let thickness = 10.0;
-            let rect_size = Vector2f::new(length, thickness);
-            let mut rect = RectangleShape::with_size(rect_size);
-            rect.set_fill_color(Color::RED);
-            let mut rect_pos = p;
-            if self.game_over_line.rotation_from_x_axis == 0.0 {
-                rect_pos.y += (self.cell_size.y - thickness) / 2.0;
-            }
-            else {
-                rect.rotate(self.game_over_line.rotation_from_x_axis);
-                if self.game_over_line.rotation_from_x_axis == 90.0 {
-                    rect_pos.x += (self.cell_size.y - thickness) / 2.0;
-                }
-            }
-            rect.set_position(rect_pos + self.position);
-            target.draw(&rect);
+        if let Some((start, end)) = self.winning_line {
+            let mut shapes = ShapeBatch::new();
+            shapes.line(start + self.position, end + self.position, Stroke::new(10.0, Color::RED).with_cap(LineCap::Round));
+            shapes.render(target, &RenderStates::DEFAULT);
         }
 
         self.should_draw_sprites = false;
@@ -29,9 +29,13 @@ fn main() {
         screen_size: app_data.win_size
     };
 
-    Application::build()
+    let result = Application::build()
         .with_initial_state(SplashScreenState::new())
         .with_states_data(init_data)
         .build(app_data)
         .run();
+
+    if let Err(error) = result {
+        eprintln!("{}", error);
+    }
 }
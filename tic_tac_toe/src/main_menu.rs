@@ -4,35 +4,9 @@ use crate::GameData;
 use crate::game_menu::GameMenuState;
 use crate::mygui::{Button, ButtonsGroup};
 
-struct MoveToAnimation {
-    initial_position: Vector2f,
-    target: Vector2f,
-    lerp_acc: f32
-}
-
-impl MoveToAnimation {
-    fn new(initial_position: Vector2f, target: Vector2f) -> Self{
-        Self {
-            initial_position,
-            target,
-            lerp_acc: 0.0
-        }
-    }
-
-    fn update(&mut self, delta: f32) -> Vector2f {
-        self.lerp_acc += delta;
-        self.lerp_acc = self.lerp_acc.clamp(0.0, 1.0);
-        (self.target - self.initial_position) * self.lerp_acc + self.initial_position
-    }
-
-    fn has_finished(&self) -> bool {
-        self.lerp_acc >= 1.0
-    }
-}
-
 pub struct MainMenuState {
     title_sprite: RcSprite,
-    move_animation: Option<MoveToAnimation>,
+    title_animation: Option<Tween<RcSprite, Vector2f>>,
     title_size: Vector2f,
     buttons: ButtonsGroup
 }
@@ -41,16 +15,16 @@ impl MainMenuState {
     pub fn new() -> Self {
         Self {
             title_sprite: RcSprite::new(),
-            move_animation: None,
+            title_animation: None,
             title_size: Vector2f::new(300.0, 300.0),
             buttons: ButtonsGroup::new()
         }
     }
 
-    fn init_title_sprite(&mut self, title_texture: &RcTexture, win_size: (u32, u32)) {
-        self.title_sprite.set_texture(title_texture, true);
+    fn init_title_sprite(&mut self, title_sprite: RcSprite, win_size: (u32, u32)) {
+        self.title_sprite = title_sprite;
         
-        let texture_size = title_texture.size().as_other::<f32>();
+        let texture_size = self.title_sprite.texture_rect().size().as_other::<f32>();
         let title_size = self.title_size;
         self.title_sprite.set_scale((
             title_size.x / texture_size.x,
@@ -66,20 +40,18 @@ impl MainMenuState {
 
 impl State<GameData> for MainMenuState {
     fn on_init(&mut self, state_data: &mut StateData<GameData>) {
-        let title_texture = state_data.assets_manager.get_asset(AssetType::Texture, "title".to_string()).unwrap();
+        let atlas: &TextureAtlas = state_data.assets_manager.get_asset(AssetType::Atlas, "sprites".to_string()).unwrap();
         let win_size = state_data.data.screen_size;
-        self.init_title_sprite(title_texture, win_size);   
+        self.init_title_sprite(atlas.sprite("title").unwrap(), win_size);   
 
         let target_pos = Vector2f::new(
             (win_size.0 as f32 - self.title_size.x) / 2.0,
             (self.title_size.y) / 3.0
         );
 
-        self.move_animation = Some(MoveToAnimation::new(self.title_sprite.position(), target_pos));
+        self.title_animation = Some(Tween::position(target_pos, 1.0).with_easing(Easing::CubicOut));
     
         
-        let btn_start_texture = state_data.assets_manager.get_asset(AssetType::Texture, "start_btn".to_string()).unwrap();
-        let btn_quit_texture = state_data.assets_manager.get_asset(AssetType::Texture, "quit_btn".to_string()).unwrap();
         let buttons_size = Vector2f::new(200.0, 100.0);
 
         let pos = (
@@ -87,11 +59,11 @@ impl State<GameData> for MainMenuState {
             (win_size.1 as f32 - target_pos.y + self.title_size.y - buttons_size.y) / 2.0
         );
 
-        let mut start_btn = Button::new(btn_start_texture, buttons_size);
+        let mut start_btn = Button::new(atlas.sprite("start_btn").unwrap(), buttons_size);
         start_btn.set_position(pos);
         self.buttons.add_button("start", start_btn);
 
-        let mut quit_btn = Button::new(btn_quit_texture, buttons_size);
+        let mut quit_btn = Button::new(atlas.sprite("quit_btn").unwrap(), buttons_size);
         quit_btn.set_position((
             pos.0,
             pos.1 + buttons_size.y + 5.0
@@ -105,17 +77,21 @@ impl State<GameData> for MainMenuState {
     fn on_event(&mut self, event: Event, state_data: &mut StateData<GameData>) -> Transition<GameData> {
         self.buttons.on_event(event);
 
+        // Cycles through the loaded languages
+        if let Event::KeyPressed { code: Key::L, .. } = event {
+            let localization = &mut state_data.localization;
+            let languages = localization.languages();
+            if let Some(index) = languages.iter().position(|language| *language == localization.language()) {
+                let next = languages[(index + 1) % languages.len()].to_string();
+                localization.set_language(&next).unwrap();
+            }
+        }
+
         Transition::None
     }
 
     fn on_update(&mut self, state_data: &mut StateData<GameData>) -> Transition<GameData> {
-        {
-            let move_animation = self.move_animation.as_mut().unwrap();
-            if !move_animation.has_finished() {
-                let pos = move_animation.update(state_data.delta_time);
-                self.title_sprite.set_position(pos);
-            }
-        }
+        self.title_animation.as_mut().unwrap().update(state_data.delta_time, &mut self.title_sprite);
 
         if self.buttons.get_button("start").is_clicked() {
             return Transition::Replace(Box::new(GameMenuState::new()));
@@ -130,7 +106,7 @@ impl State<GameData> for MainMenuState {
     fn on_render(&mut self, state_data: &mut StateData<GameData>, window: &mut dyn RenderTarget) -> bool {
         window.clear(state_data.data.clear_color);
         window.draw(&self.title_sprite);
-        if self.move_animation.as_ref().unwrap().has_finished() {
+        if self.title_animation.as_ref().unwrap().is_finished() {
             self.buttons.draw(window);
         }
 
@@ -8,10 +8,8 @@ pub struct Button {
 }
 
 impl Button {
-    pub fn new(texture: &RcTexture, size: impl Into<Vector2f>) -> Self {
-        let mut sprite = RcSprite::with_texture(texture);
-
-        let texture_size = texture.size().as_other::<f32>();
+    pub fn new(mut sprite: RcSprite, size: impl Into<Vector2f>) -> Self {
+        let texture_size = sprite.texture_rect().size().as_other::<f32>();
         let size = Vector2f::from(size.into());
 
         sprite.set_scale((
@@ -1,3 +1,4 @@
+use std::time::Duration;
 use sfmx::prelude::*;
 use crate::{main_menu::MainMenuState, GameData};
 
@@ -8,22 +9,20 @@ const MOVE_NEXT_STATE_TIME: f32 = 0.0;
 const MOVE_NEXT_STATE_TIME: f32 = 2.0;
 
 pub struct SplashScreenState {
-    title_sprite: RcSprite,
-    timer: Timer
+    title_sprite: RcSprite
 }
 
 impl SplashScreenState {
     pub fn new() -> Self {
         Self {
-            title_sprite: RcSprite::new(),
-            timer: Timer::new()
+            title_sprite: RcSprite::new()
         }
     }
 
-    fn init_title_sprite(&mut self, title_texture: &RcTexture, win_size: (u32, u32)) {
-        self.title_sprite.set_texture(title_texture, true);
+    fn init_title_sprite(&mut self, title_sprite: RcSprite, win_size: (u32, u32)) {
+        self.title_sprite = title_sprite;
         let title_size = Vector2f::new(320.0, 320.0);
-        let texture_size = title_texture.size().as_other::<f32>();
+        let texture_size = self.title_sprite.texture_rect().size().as_other::<f32>();
         self.title_sprite.set_scale((
             title_size.x / texture_size.x,
             title_size.y / texture_size.y
@@ -38,36 +37,46 @@ impl SplashScreenState {
 impl State<GameData> for SplashScreenState {
     
     fn on_init(&mut self, state_data: &mut StateData<GameData>) {
-        //Game assets
-        state_data.assets_manager.load_asset_buffered(AssetType::Texture, "X".to_string(), "assets/textures/icon_x.png");
-        state_data.assets_manager.load_asset_buffered(AssetType::Texture, "O".to_string(), "assets/textures/icon_o.png");
-        state_data.assets_manager.load_asset_buffered(AssetType::Texture, "white".to_string(), "assets/textures/white_texture.png");
-        state_data.assets_manager.load_asset_buffered(AssetType::Texture, "start_btn".to_string(), "assets/textures/start_btn.png");
-        state_data.assets_manager.load_asset_buffered(AssetType::Texture, "quit_btn".to_string(), "assets/textures/quit_btn.png");
-        state_data.assets_manager.load_asset_buffered(AssetType::Texture, "restart_btn".to_string(), "assets/textures/restart_btn.png");
-        state_data.assets_manager.load_asset_buffered(AssetType::Texture, "arrow".to_string(), "assets/textures/arrow.png");
+        // The assets are next to the executable of a shipped build, in the crate while developing
+        #[cfg(debug_assertions)]
+        state_data.assets_manager.mount(DirectorySource::new(env!("CARGO_MANIFEST_DIR")));
+        match DirectorySource::next_to_executable() {
+            Ok(source) => state_data.assets_manager.mount(source),
+            Err(error) => eprintln!("{}", error)
+        }
+        state_data.capture.set_screenshot_key(Some(Key::F12));
 
-        state_data.assets_manager.load_asset_buffered(AssetType::Font, "font".to_string(), "assets/fonts/Silkscreen/slkscre.ttf");
+        for language in ["en", "fr"] {
+            let path = format!("assets/lang/{}.lang", language);
+            if let Err(error) = state_data.localization.load_language(language, state_data.assets_manager.file_system(), &path) {
+                eprintln!("{}", error);
+            }
+        }
         ////////////
 
-        // State assets
-        state_data.assets_manager.load_asset(AssetType::Texture, "title".to_string(), "assets/textures/title.png");
+        // All the sprites, the title is needed right away
+        state_data.assets_manager.load_asset(AssetType::Atlas, "sprites".to_string(), "assets/textures/sprites.atlas");
         ////
 
-        let title_texture = state_data.assets_manager.get_asset(AssetType::Texture, "title".to_string()).unwrap();
+        let atlas: &TextureAtlas = state_data.assets_manager.get_asset(AssetType::Atlas, "sprites".to_string()).unwrap();
         let win_size = state_data.data.screen_size;
-        self.init_title_sprite(title_texture, win_size);
-        self.timer.restart();
-    }
-
-    fn on_update(&mut self, state_data: &mut StateData<GameData>) -> Transition<GameData> {
+        self.init_title_sprite(atlas.sprite("title").unwrap(), win_size);
 
-        state_data.assets_manager.launch_loadings();
+        // The title is shown for a while, then the game assets are loaded
+        let clear_color = state_data.data.clear_color;
+        let spawned = state_data.tasks.spawn_transition(async move {
+            wait(Duration::from_secs_f32(MOVE_NEXT_STATE_TIME)).await;
 
-        if self.timer.elapsed().as_secs_f32() > MOVE_NEXT_STATE_TIME  && state_data.assets_manager.loading_percentage() >= 100.0{
-            return Transition::Replace(Box::new(MainMenuState::new()));
+            let game_assets = AssetsBundle::new()
+                .with_asset(AssetType::Font, "font", "assets/fonts/Silkscreen/slkscre.ttf");
+            let loading = LoadingState::new(Box::new(MainMenuState::new()))
+                .with_bundle(game_assets)
+                .with_colors(clear_color, Color::BLACK);
+            Transition::Replace(Box::new(loading))
+        });
+        if let Err(error) = spawned {
+            eprintln!("{}", error);
         }
-        Transition::None
     }
 
     fn on_render(&mut self, state_data: &mut StateData<GameData>, window: &mut dyn RenderTarget) -> bool {